@@ -0,0 +1,441 @@
+use crate::{check_gear_ratio, Matrix};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum EditError {
+    OutOfBounds { row: usize, column: usize },
+    NumberTooLarge { row: usize, column: usize },
+    GearRatioTooLarge { row: usize, column: usize },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditError::OutOfBounds { row, column } => {
+                write!(f, "no cell at {},{}", row, column)
+            }
+            EditError::NumberTooLarge { row, column } => {
+                write!(
+                    f,
+                    "edit would make the number at {},{} too large",
+                    row, column
+                )
+            }
+            EditError::GearRatioTooLarge { row, column } => {
+                write!(
+                    f,
+                    "edit would make the gear ratio at {},{} too large",
+                    row, column
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+impl Matrix {
+    // replace one cell, touching only the numbers on either side of it and its own symbol state
+    // an edit leaving a number or gear ratio too large for parse to accept is refused and the
+    // matrix left as it was
+    pub fn set_cell(&mut self, i: usize, j: usize, c: char) -> Result<(), EditError> {
+        let old = self
+            .get(i, j)
+            .ok_or(EditError::OutOfBounds { row: i, column: j })?;
+        if old == c {
+            return Ok(());
+        }
+
+        // the numbers the span will hold, read before anything changes
+        let (start, end) = self.edit_span(i, j);
+        let mut span = self.data[i][start..end].to_vec();
+        span[j - start] = c;
+        if let Some(num) = Matrix::get_numbers_in_line(i, &span)
+            .iter()
+            .find(|num| num.value.is_none())
+        {
+            return Err(EditError::NumberTooLarge {
+                row: i,
+                column: start + num.start,
+            });
+        }
+
+        self.replace_cell(i, j, c);
+
+        // only gears within reach of the span can see different numbers, an edit pushing one of
+        // their ratios past u64 is put back, which reads the same numbers back in
+        let reach: Vec<(usize, usize)> = (start..end)
+            .flat_map(|y| std::iter::once((i, y)).chain(self.neighbours(i, y)))
+            .collect();
+        if let Some(&(row, column)) = reach
+            .iter()
+            .find(|&&(x, y)| self.is_gear(x, y) && !self.gear_ratio_fits(x, y))
+        {
+            self.replace_cell(i, j, old);
+            return Err(EditError::GearRatioTooLarge { row, column });
+        }
+
+        Ok(())
+    }
+
+    fn replace_cell(&mut self, i: usize, j: usize, c: char) {
+        let old = self.data[i][j];
+        self.data[i][j] = c;
+
+        if old.is_ascii_digit() || c.is_ascii_digit() {
+            self.renumber_span(i, j);
+        }
+    }
+
+    // the columns of row i whose numbers can change when (i, j) changes, the runs of digits
+    // either side of j plus j itself
+    fn edit_span(&self, i: usize, j: usize) -> (usize, usize) {
+        let line = &self.data[i];
+
+        let mut start = j;
+        while start > 0 && line[start - 1].is_ascii_digit() {
+            start -= 1;
+        }
+        let mut end = j + 1;
+        while end < line.len() && line[end].is_ascii_digit() {
+            end += 1;
+        }
+
+        (start, end)
+    }
+
+    fn renumber_span(&mut self, i: usize, j: usize) {
+        let (start, end) = self.edit_span(i, j);
+
+        // drop the numbers that used to sit in the span, highest id first so that swap_remove
+        // never moves a number that is about to be removed
+        let mut removed: Vec<usize> = (start..end).filter_map(|y| self.number_at(i, y)).collect();
+        removed.sort();
+        removed.dedup();
+
+        for &id in removed.iter().rev() {
+            for y in start..end {
+                if self.number_at(i, y) == Some(id) {
+                    self.set_number_at(i, y, None);
+                }
+            }
+
+            self.numbers.swap_remove(id);
+            if id < self.numbers.len() {
+                // the last number now lives at id
+                for (x, y) in self.numbers[id].coordinates().collect::<Vec<_>>() {
+                    self.set_number_at(x, y, Some(id));
+                }
+            }
+        }
+
+        // and read them back in from the edited line
+        let mut added = Matrix::get_numbers_in_line(i, &self.data[i][start..end]);
+        for num in added.iter_mut() {
+            num.start += start;
+        }
+
+        for num in added {
+            let id = self.numbers.len();
+            for (x, y) in num.coordinates() {
+                self.set_number_at(x, y, Some(id));
+            }
+            self.numbers.push(num);
+        }
+    }
+}
+
+// a matrix with its part number and gear ratio sums kept current through every edit
+#[derive(Clone, Debug)]
+pub struct LiveMatrix {
+    matrix: Matrix,
+    part_number_sum: u64,
+    gear_ratio_sum: u64,
+}
+
+impl LiveMatrix {
+    pub fn new(matrix: Matrix) -> LiveMatrix {
+        let mut live = LiveMatrix {
+            matrix,
+            part_number_sum: 0,
+            gear_ratio_sum: 0,
+        };
+
+        let (part_number_sum, gear_ratio_sum) = live.sums_over(None);
+        live.part_number_sum = part_number_sum;
+        live.gear_ratio_sum = gear_ratio_sum;
+
+        live
+    }
+
+    pub fn matrix(&self) -> &Matrix {
+        &self.matrix
+    }
+
+    pub fn part_number_sum(&self) -> u64 {
+        self.part_number_sum
+    }
+
+    pub fn gear_ratio_sum(&self) -> u64 {
+        self.gear_ratio_sum
+    }
+
+    pub fn set_cell(&mut self, i: usize, j: usize, c: char) -> Result<(), EditError> {
+        if self.matrix.get(i, j).is_none() {
+            return Err(EditError::OutOfBounds { row: i, column: j });
+        }
+
+        // only numbers and gears within reach of the edited span can change their contribution,
+        // so take theirs out, make the edit, and add back what they contribute now
+        let region = self.affected_region(i, j);
+
+        let (parts_before, gears_before) = self.sums_over(Some(&region));
+        self.matrix.set_cell(i, j, c)?;
+        let (parts_after, gears_after) = self.sums_over(Some(&region));
+
+        self.part_number_sum = self.part_number_sum - parts_before + parts_after;
+        self.gear_ratio_sum = self.gear_ratio_sum - gears_before + gears_after;
+
+        Ok(())
+    }
+
+    // the edit span and every cell touching it, the same before and after the edit because the
+    // span is bounded by the digits either side of (i, j), which the edit leaves alone
+    fn affected_region(&self, i: usize, j: usize) -> HashSet<(usize, usize)> {
+        let (start, end) = self.matrix.edit_span(i, j);
+
+        (start..end)
+            .flat_map(|y| {
+                std::iter::once((i, y)).chain(self.matrix.neighbours(i, y).collect::<Vec<_>>())
+            })
+            .collect()
+    }
+
+    // part numbers with a digit in the region and gear ratios of gears in the region,
+    // or of the whole matrix when there is no region
+    fn sums_over(&self, region: Option<&HashSet<(usize, usize)>>) -> (u64, u64) {
+        let matrix = &self.matrix;
+
+        let number_ids: HashSet<usize> = match region {
+            Some(cells) => cells
+                .iter()
+                .filter_map(|&(x, y)| matrix.number_at(x, y))
+                .collect(),
+            None => (0..matrix.numbers.len()).collect(),
+        };
+        let part_number_sum = number_ids
+            .into_iter()
+            .map(|id| &matrix.numbers[id])
+            .filter(|num| matrix.is_part_number(num))
+            .map(|num| num.number() as u64)
+            .sum();
+
+        let gear_ratio_sum = match region {
+            Some(cells) => cells
+                .iter()
+                .filter(|&&(x, y)| matrix.is_gear(x, y))
+                .filter_map(|x| check_gear_ratio(matrix, x))
+                .sum(),
+            None => matrix
+                .gear_coordinates()
+                .filter_map(|x| check_gear_ratio(matrix, &x))
+                .sum(),
+        };
+
+        (part_number_sum, gear_ratio_sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neighbourhood::Neighbourhood;
+    use crate::rules::Rules;
+    use crate::{get_all_gear_ratios, get_part_numbers};
+    use proptest::prelude::*;
+
+    const TEST_DATA: &str = include_str!("../test_data.txt");
+
+    fn rebuild(matrix: &Matrix) -> Matrix {
+        let mut rebuilt = Matrix::new();
+        rebuilt.rules = matrix.rules.clone();
+        rebuilt.populate_data(matrix.data.clone());
+        rebuilt.populate_from_data();
+        rebuilt
+    }
+
+    // (row, start, length, value) of every number, in reading order
+    fn number_spans(matrix: &Matrix) -> Vec<(usize, usize, usize, Option<u32>)> {
+        let mut result: Vec<_> = matrix
+            .numbers
+            .iter()
+            .map(|num| (num.row, num.start, num.len, num.value))
+            .collect();
+        result.sort();
+        result
+    }
+
+    fn assert_matches_rebuild(live: &LiveMatrix) {
+        let matrix = live.matrix();
+        let rebuilt = rebuild(matrix);
+
+        assert_eq!(number_spans(matrix), number_spans(&rebuilt));
+
+        // every digit points at the number covering it and nothing else points anywhere
+        for (i, line) in matrix.data.iter().enumerate() {
+            for (j, c) in line.iter().enumerate() {
+                match matrix.number_at(i, j) {
+                    Some(id) => assert!(matrix.numbers[id].contains(&(i, j))),
+                    None => assert!(!c.is_ascii_digit()),
+                }
+            }
+        }
+
+        let part_number_sum: u64 = get_part_numbers(&rebuilt).iter().map(|&x| x as u64).sum();
+        assert_eq!(live.part_number_sum(), part_number_sum);
+        assert_eq!(live.gear_ratio_sum(), get_all_gear_ratios(&rebuilt));
+    }
+
+    #[test]
+    fn test_set_cell_splits_and_joins_numbers() {
+        let mut live = LiveMatrix::new(Matrix::parse(TEST_DATA, Rules::default()).unwrap());
+        assert_eq!(
+            (live.part_number_sum(), live.gear_ratio_sum()),
+            (4361, 467835)
+        );
+
+        // 467 -> 4.7, only the 7 still touches the gear
+        live.set_cell(0, 1, '.').unwrap();
+        assert_eq!(live.part_number_sum(), 4361 - 467 + 7);
+        assert_eq!(live.gear_ratio_sum(), 467835 - 16345 + 7 * 35);
+        assert_matches_rebuild(&live);
+
+        // 4.7 -> 417
+        live.set_cell(0, 1, '1').unwrap();
+        assert_eq!(live.part_number_sum(), 4361 - 467 + 417);
+        assert_eq!(live.gear_ratio_sum(), 467835 - 16345 + 417 * 35);
+        assert_matches_rebuild(&live);
+
+        // a new '*' between 114 and 633 makes 114 a part number and adds a gear
+        live.set_cell(1, 7, '*').unwrap();
+        assert_eq!(live.part_number_sum(), 4361 - 467 + 417 + 114);
+        assert_eq!(live.gear_ratio_sum(), 467835 - 16345 + 417 * 35 + 114 * 633);
+        assert_matches_rebuild(&live);
+    }
+
+    #[test]
+    fn test_set_cell_removes_symbol() {
+        let mut live = LiveMatrix::new(Matrix::parse(TEST_DATA, Rules::default()).unwrap());
+
+        // 617's only symbol
+        live.set_cell(4, 3, '.').unwrap();
+        assert_eq!(live.part_number_sum(), 4361 - 617);
+        assert!(!live.matrix().is_symbol(4, 3));
+        assert!(!live.matrix().is_gear(4, 3));
+        assert_matches_rebuild(&live);
+    }
+
+    #[test]
+    fn test_set_cell_out_of_bounds() {
+        let mut live = LiveMatrix::new(
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap(),
+        );
+
+        assert_eq!(
+            live.set_cell(4, 4, '1'),
+            Err(EditError::OutOfBounds { row: 4, column: 4 })
+        );
+        assert_eq!(
+            live.set_cell(5, 0, '1'),
+            Err(EditError::OutOfBounds { row: 5, column: 0 })
+        );
+        assert_matches_rebuild(&live);
+    }
+
+    #[test]
+    fn test_set_cell_number_too_large() {
+        let mut live = LiveMatrix::new("*..........\n123456.7890".parse().unwrap());
+        let before = live.matrix().clone();
+
+        // 123456 and 7890 would join into 12345697890
+        assert_eq!(
+            live.set_cell(1, 6, '9'),
+            Err(EditError::NumberTooLarge { row: 1, column: 0 })
+        );
+        assert_eq!(number_spans(live.matrix()), number_spans(&before));
+        assert_eq!(live.matrix().data, before.data);
+        assert_eq!(live.part_number_sum(), 123456);
+
+        // still editable, and the same cell can take a non-digit
+        live.set_cell(1, 6, '#').unwrap();
+        assert_eq!(live.part_number_sum(), 123456 + 7890);
+        assert_matches_rebuild(&live);
+    }
+
+    #[test]
+    fn test_set_cell_gear_ratio_too_large() {
+        let input = "999999999.999999999\n...................\n999999999..........\n";
+        let rules = "neighbours = at least 3".parse::<Rules>().unwrap();
+        let mut live = LiveMatrix::new(Matrix::parse(input, rules).unwrap());
+
+        assert_eq!(
+            live.set_cell(1, 9, '*'),
+            Err(EditError::GearRatioTooLarge { row: 1, column: 9 })
+        );
+        assert_eq!(live.matrix().get(1, 9), Some('.'));
+        assert_eq!(live.matrix().gear_coordinates().count(), 0);
+        assert_eq!(live.gear_ratio_sum(), 0);
+        assert_matches_rebuild(&live);
+    }
+
+    fn cell() -> impl Strategy<Value = char> {
+        prop::sample::select(vec![
+            '.', '.', '.', '.', '1', '2', '5', '9', '0', '*', '#', '$',
+        ])
+    }
+
+    fn neighbourhood() -> impl Strategy<Value = Neighbourhood> {
+        prop::sample::select(vec![
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Moore,
+            Neighbourhood::MooreRadius(2),
+            Neighbourhood::Toroidal,
+        ])
+    }
+
+    // ragged grids of up to 8 x 8 cells
+    fn grid() -> impl Strategy<Value = Vec<Vec<char>>> {
+        prop::collection::vec(prop::collection::vec(cell(), 1..8), 1..8)
+    }
+
+    proptest! {
+        #[test]
+        fn set_cell_matches_rebuild(
+            data in grid(),
+            neighbourhood in neighbourhood(),
+            edits in prop::collection::vec((0..8usize, 0..8usize, cell()), 1..24),
+        ) {
+            let rules = Rules {
+                neighbourhood,
+                ..Rules::default()
+            };
+
+            let mut matrix = Matrix::new();
+            matrix.rules = rules;
+            matrix.populate_data(data);
+            matrix.populate_from_data();
+
+            let mut live = LiveMatrix::new(matrix);
+
+            for (i, j, c) in edits {
+                // fold the cell into the grid so most edits land
+                let i = i % live.matrix().height();
+                let j = j % live.matrix().data[i].len();
+
+                live.set_cell(i, j, c).unwrap();
+                assert_matches_rebuild(&live);
+            }
+        }
+    }
+}
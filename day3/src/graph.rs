@@ -0,0 +1,409 @@
+use crate::Matrix;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NumberNode {
+    pub id: usize,
+    pub value: u32,
+    pub row: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SymbolNode {
+    pub id: usize,
+    pub symbol: char,
+    pub row: usize,
+    pub column: usize,
+}
+
+// one machine: a set of numbers and symbols linked by adjacency
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Component {
+    pub numbers: Vec<usize>,
+    pub symbols: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DegreeStats {
+    pub symbol: char,
+    pub count: usize,
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    // degree -> number of symbols with that degree
+    pub histogram: BTreeMap<usize, usize>,
+}
+
+// bipartite graph with an edge between each number and every symbol touching it
+#[derive(Clone, Debug)]
+pub struct Graph {
+    pub numbers: Vec<NumberNode>,
+    pub symbols: Vec<SymbolNode>,
+    // (number id, symbol id) pairs
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    pub fn build_from(matrix: &Matrix) -> Graph {
+        let numbers: Vec<NumberNode> = matrix
+            .numbers
+            .iter()
+            .enumerate()
+            .map(|(id, num)| NumberNode {
+                id,
+                value: num.number(),
+                row: num.row,
+                column: num.start,
+                length: num.len,
+            })
+            .collect();
+
+        let mut symbols: Vec<SymbolNode> = Vec::new();
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+
+        // reading order, so ids are stable between runs
+        for (id, (i, j)) in matrix.symbol_coordinates().enumerate() {
+            symbols.push(SymbolNode {
+                id,
+                symbol: matrix.data[i][j],
+                row: i,
+                column: j,
+            });
+
+            edges.extend(matrix.adjacent_numbers(i, j).into_iter().map(|n| (n, id)));
+        }
+        edges.sort();
+
+        Graph {
+            numbers,
+            symbols,
+            edges,
+        }
+    }
+
+    // numbers take node ids 0..n and symbols follow on from n
+    fn symbol_node(&self, symbol_id: usize) -> usize {
+        self.numbers.len() + symbol_id
+    }
+
+    pub fn components(&self) -> Vec<Component> {
+        let node_count = self.numbers.len() + self.symbols.len();
+        let mut parent: Vec<usize> = (0..node_count).collect();
+
+        for &(n, s) in &self.edges {
+            let (a, b) = (find(&mut parent, n), find(&mut parent, self.symbol_node(s)));
+            parent[a] = b;
+        }
+
+        let mut groups: BTreeMap<usize, Component> = BTreeMap::new();
+
+        for node in 0..node_count {
+            let component = groups
+                .entry(find(&mut parent, node))
+                .or_insert_with(|| Component {
+                    numbers: Vec::new(),
+                    symbols: Vec::new(),
+                });
+
+            match node < self.numbers.len() {
+                true => component.numbers.push(node),
+                false => component.symbols.push(node - self.numbers.len()),
+            }
+        }
+
+        // order by the first number, symbols without any number go last
+        let mut result: Vec<Component> = groups.into_values().collect();
+        result.sort_by_key(|c| {
+            (
+                c.numbers.first().copied().unwrap_or(usize::MAX),
+                c.symbols.first().copied(),
+            )
+        });
+
+        result
+    }
+
+    // the component holding the number or symbol at (i, j)
+    pub fn component_at(&self, i: usize, j: usize) -> Option<Component> {
+        let number = self
+            .numbers
+            .iter()
+            .find(|n| n.row == i && (n.column..n.column + n.length).contains(&j));
+        let symbol = self.symbols.iter().find(|s| (s.row, s.column) == (i, j));
+
+        self.components()
+            .into_iter()
+            .find(|c| match (number, symbol) {
+                (Some(n), _) => c.numbers.contains(&n.id),
+                (_, Some(s)) => c.symbols.contains(&s.id),
+                _ => false,
+            })
+    }
+
+    pub fn degree_stats(&self) -> Vec<DegreeStats> {
+        let mut degrees: Vec<usize> = vec![0; self.symbols.len()];
+        for &(_, s) in &self.edges {
+            degrees[s] += 1;
+        }
+
+        let mut by_symbol: BTreeMap<char, Vec<usize>> = BTreeMap::new();
+        for symbol in &self.symbols {
+            by_symbol
+                .entry(symbol.symbol)
+                .or_default()
+                .push(degrees[symbol.id]);
+        }
+
+        by_symbol
+            .into_iter()
+            .map(|(symbol, degrees)| {
+                let mut histogram: BTreeMap<usize, usize> = BTreeMap::new();
+                for &d in &degrees {
+                    *histogram.entry(d).or_default() += 1;
+                }
+
+                DegreeStats {
+                    symbol,
+                    count: degrees.len(),
+                    min: *degrees.iter().min().unwrap(),
+                    max: *degrees.iter().max().unwrap(),
+                    mean: degrees.iter().sum::<usize>() as f64 / degrees.len() as f64,
+                    histogram,
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("graph schematic {\n");
+
+        for (k, component) in self.components().iter().enumerate() {
+            writeln!(result, "  subgraph cluster_{} {{", k).unwrap();
+            for &n in &component.numbers {
+                let node = &self.numbers[n];
+                writeln!(
+                    result,
+                    "    n{} [label=\"{}\", shape=box, tooltip=\"{},{}\"];",
+                    n, node.value, node.row, node.column
+                )
+                .unwrap();
+            }
+            for &s in &component.symbols {
+                let node = &self.symbols[s];
+                writeln!(
+                    result,
+                    "    s{} [label=\"{}\", shape=circle, tooltip=\"{},{}\"];",
+                    s,
+                    escape_dot(node.symbol),
+                    node.row,
+                    node.column
+                )
+                .unwrap();
+            }
+            result.push_str("  }\n");
+        }
+
+        for &(n, s) in &self.edges {
+            writeln!(result, "  n{} -- s{};", n, s).unwrap();
+        }
+
+        result.push_str("}\n");
+        result
+    }
+
+    pub fn to_json(&self) -> String {
+        let value = json!({
+            "numbers": self.numbers,
+            "symbols": self.symbols,
+            "edges": self.edges,
+            "components": self.components(),
+            "degree_stats": self.degree_stats(),
+        });
+
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    pub fn summary(&self) -> String {
+        let components = self.components();
+        let machines = components.iter().filter(|c| !c.symbols.is_empty()).count();
+        let mut result = String::new();
+
+        writeln!(
+            result,
+            "{} numbers, {} symbols, {} edges",
+            self.numbers.len(),
+            self.symbols.len(),
+            self.edges.len()
+        )
+        .unwrap();
+        writeln!(
+            result,
+            "{} components, {} with at least one symbol",
+            components.len(),
+            machines
+        )
+        .unwrap();
+
+        writeln!(result, "symbol  count  min  max   mean").unwrap();
+        for stats in self.degree_stats() {
+            writeln!(
+                result,
+                "{:>6}  {:>5}  {:>3}  {:>3}  {:>5.2}",
+                stats.symbol, stats.count, stats.min, stats.max, stats.mean
+            )
+            .unwrap();
+        }
+
+        result
+    }
+}
+
+// union find root with path halving
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+fn escape_dot(c: char) -> String {
+    match c {
+        '"' | '\\' => format!("\\{}", c),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+
+    const TEST_DATA: &str = include_str!("../test_data.txt");
+
+    fn test_graph() -> Graph {
+        Graph::build_from(&Matrix::parse(TEST_DATA, Rules::default()).unwrap())
+    }
+
+    #[test]
+    fn test_build_from() {
+        let graph = test_graph();
+
+        assert_eq!(graph.numbers.len(), 10);
+        assert_eq!(
+            graph.numbers[2],
+            NumberNode {
+                id: 2,
+                value: 35,
+                row: 2,
+                column: 2,
+                length: 2
+            }
+        );
+        assert_eq!(
+            graph.symbols.iter().map(|s| s.symbol).collect::<String>(),
+            "*#*+$*"
+        );
+        assert_eq!(
+            graph.edges,
+            vec![
+                (0, 0),
+                (2, 0),
+                (3, 1),
+                (4, 2),
+                (6, 3),
+                (7, 5),
+                (8, 4),
+                (9, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_components() {
+        let components = test_graph().components();
+
+        assert_eq!(components.len(), 8);
+        assert_eq!(
+            components[0],
+            Component {
+                numbers: vec![0, 2],
+                symbols: vec![0]
+            }
+        );
+        // 114 touches nothing
+        assert_eq!(
+            components[1],
+            Component {
+                numbers: vec![1],
+                symbols: vec![]
+            }
+        );
+        assert_eq!(
+            components[6],
+            Component {
+                numbers: vec![7, 9],
+                symbols: vec![5]
+            }
+        );
+    }
+
+    #[test]
+    fn test_component_at() {
+        let graph = test_graph();
+
+        // from a digit of 598 and from the gear between 755 and 598
+        let expected = Component {
+            numbers: vec![7, 9],
+            symbols: vec![5],
+        };
+        assert_eq!(graph.component_at(9, 7), Some(expected.clone()));
+        assert_eq!(graph.component_at(8, 5), Some(expected));
+        assert_eq!(graph.component_at(0, 3), None);
+    }
+
+    #[test]
+    fn test_degree_stats() {
+        let stats = test_graph().degree_stats();
+
+        assert_eq!(stats.iter().map(|s| s.symbol).collect::<String>(), "#$*+");
+
+        let gears = &stats[2];
+        assert_eq!((gears.count, gears.min, gears.max), (3, 1, 2));
+        assert!((gears.mean - 5.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            gears.histogram,
+            vec![(1, 1), (2, 2)].into_iter().collect::<BTreeMap<_, _>>()
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = test_graph().to_dot();
+
+        assert!(dot.starts_with("graph schematic {\n  subgraph cluster_0 {\n"));
+        assert!(dot.contains("    n0 [label=\"467\", shape=box, tooltip=\"0,0\"];\n"));
+        assert!(dot.contains("    s3 [label=\"+\", shape=circle, tooltip=\"5,5\"];\n"));
+        assert!(dot.contains("  n9 -- s5;\n"));
+        assert_eq!(dot.matches(" -- ").count(), 8);
+        assert!(dot.ends_with("}\n"));
+
+        assert_eq!(escape_dot('"'), "\\\"");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&test_graph().to_json()).unwrap();
+
+        assert_eq!(json["numbers"][0]["value"], 467);
+        assert_eq!(json["symbols"][1]["symbol"], "#");
+        assert_eq!(json["edges"][7], serde_json::json!([9, 5]));
+        assert_eq!(json["components"].as_array().unwrap().len(), 8);
+        assert_eq!(json["degree_stats"][2]["histogram"]["2"], 2);
+    }
+}
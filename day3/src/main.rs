@@ -1,99 +1,340 @@
-use itertools::iproduct;
-use std::collections::HashSet;
+mod edit;
+mod graph;
+mod neighbourhood;
+mod render;
+mod report;
+mod rules;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use edit::LiveMatrix;
+use graph::Graph;
+use neighbourhood::Neighbourhood;
+use report::Report;
+use rules::Rules;
+use std::fmt;
 use std::fs;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Schematic to evaluate, or - to read it from stdin
+    #[arg(long, global = true, default_value = "data.txt")]
+    input: String,
+
+    /// File of symbol and gear rules, the puzzle's rules when left out
+    #[arg(long, global = true)]
+    rules: Option<String>,
+
+    /// Cells counted as touching: von-neumann, moore, moore:R or toroidal, overrides the rules file
+    #[arg(long, global = true)]
+    neighbourhood: Option<Neighbourhood>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the part number sum and gear ratio sum, the default
+    Solve,
+    /// Print the schematic with cells coloured by role
+    Render {
+        /// Also write the schematic as an SVG to this file
+        #[arg(long)]
+        svg: Option<String>,
+
+        /// Outline the perimeter of the number covering this cell, given as ROW,COLUMN
+        #[arg(long, value_parser = parse_cell)]
+        highlight: Option<(usize, usize)>,
+    },
+    /// Analyse the graph linking numbers to the symbols they touch
+    Graph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Summary)]
+        format: GraphFormat,
+
+        /// List the machine holding the number or symbol at this cell, given as ROW,COLUMN
+        #[arg(long, value_parser = parse_cell)]
+        machine: Option<(usize, usize)>,
+    },
+
+    /// Change cells one at a time, printing both sums after each change
+    Edit {
+        /// A change given as ROW,COLUMN=CHAR, applied in order
+        #[arg(long = "set", value_parser = parse_edit, required = true)]
+        edits: Vec<(usize, usize, char)>,
+
+        /// Write the edited schematic to this file
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// List the numbers touching no symbol, the symbols touching no number and the failing gears
+    Report {
+        /// Print the report as JSON rather than a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GraphFormat {
+    Summary,
+    Dot,
+    Json,
+}
+
+// Example input: "0,2"
+fn parse_cell(s: &str) -> Result<(usize, usize), String> {
+    let (i, j) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected ROW,COLUMN, found `{}`", s))?;
+
+    let parse = |x: &str| {
+        x.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid coordinate `{}`", x))
+    };
+
+    Ok((parse(i)?, parse(j)?))
+}
+
+// Example input: "1,3=#"
+fn parse_edit(s: &str) -> Result<(usize, usize, char), String> {
+    let (cell, c) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected ROW,COLUMN=CHAR, found `{}`", s))?;
+    let (i, j) = parse_cell(cell)?;
+
+    let mut chars = c.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok((i, j, c)),
+        _ => Err(format!("expected a single character, found `{}`", c)),
+    }
+}
 
 fn main() {
-    let matrix = Matrix::build_from_file("data.txt");
+    let args = Args::parse();
+
+    let mut rules = match &args.rules {
+        Some(filepath) => Rules::from_file(filepath).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        None => Rules::default(),
+    };
+    if let Some(neighbourhood) = args.neighbourhood {
+        rules.neighbourhood = neighbourhood;
+    }
 
-    let result = get_all_gear_ratios(&matrix);
+    let matrix = match args.input.as_str() {
+        "-" => Matrix::from_reader(io::stdin().lock(), rules),
+        filepath => Matrix::build_from_file(filepath, rules),
+    }
+    .unwrap_or_else(|e| {
+        eprintln!("{}: {}", args.input, e);
+        std::process::exit(1);
+    });
+
+    match args.command.unwrap_or(Command::Solve) {
+        Command::Solve => {
+            println!("part 1: {}", part_number_sum(&matrix));
+
+            let result = get_all_gear_ratios(&matrix);
+            println!("part 2: {}", result);
+        }
+        Command::Render { svg, highlight } => {
+            let highlight = highlight.map(|(i, j)| {
+                matrix.number_at(i, j).unwrap_or_else(|| {
+                    eprintln!("no number at {},{}", i, j);
+                    std::process::exit(1);
+                })
+            });
+
+            print!("{}", render::ansi(&matrix, highlight));
+
+            if let Some(filepath) = svg {
+                fs::write(&filepath, render::svg(&matrix, highlight))
+                    .expect("unable to write svg to file");
+            }
+        }
+        Command::Graph { format, machine } => {
+            let graph = Graph::build_from(&matrix);
+
+            if let Some((i, j)) = machine {
+                let component = graph.component_at(i, j).unwrap_or_else(|| {
+                    eprintln!("no number or symbol at {},{}", i, j);
+                    std::process::exit(1);
+                });
+
+                for &s in &component.symbols {
+                    let node = &graph.symbols[s];
+                    println!("{} at {},{}", node.symbol, node.row, node.column);
+                }
+                for &n in &component.numbers {
+                    let node = &graph.numbers[n];
+                    println!("{} at {},{}", node.value, node.row, node.column);
+                }
+                return;
+            }
+
+            match format {
+                GraphFormat::Summary => print!("{}", graph.summary()),
+                GraphFormat::Dot => print!("{}", graph.to_dot()),
+                GraphFormat::Json => println!("{}", graph.to_json()),
+            }
+        }
+        Command::Edit { edits, output } => {
+            let mut live = LiveMatrix::new(matrix);
+
+            for (i, j, c) in edits {
+                if let Err(e) = live.set_cell(i, j, c) {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+                println!(
+                    "{},{}={}  part 1: {}  part 2: {}",
+                    i,
+                    j,
+                    c,
+                    live.part_number_sum(),
+                    live.gear_ratio_sum()
+                );
+            }
+
+            if let Some(filepath) = output {
+                let schematic: String = live
+                    .matrix()
+                    .data
+                    .iter()
+                    .map(|line| line.iter().collect::<String>() + "\n")
+                    .collect();
+                fs::write(&filepath, schematic).expect("unable to write schematic to file");
+            }
+        }
+        Command::Report { json } => {
+            let report = Report::build_from(&matrix);
 
-    println!("{}", result);
+            match json {
+                true => println!("{}", report.to_json()),
+                false => print!("{}", report.to_table()),
+            }
+        }
+    }
 }
 
-fn get_all_gear_ratios(matrix: &Matrix) -> u32 {
-    let mut gear_ratios: Vec<u32> = Vec::new();
+fn get_part_numbers(matrix: &Matrix) -> Vec<u32> {
+    let mut result: Vec<u32> = Vec::new();
 
-    for gear_coordinates in &matrix.symbol_coordinates {
-        match check_gear_ratio(matrix, gear_coordinates) {
-            Some(gear_ratio) => {
-                gear_ratios.push(gear_ratio);
-            },
-            None => {},
+    for num in &matrix.numbers {
+        if matrix.is_part_number(num) {
+            result.push(num.number());
         }
     }
 
-    gear_ratios.iter().sum::<u32>()
+    result
+}
+
+// summed in u64, a handful of large part numbers already overflow a u32
+fn part_number_sum(matrix: &Matrix) -> u64 {
+    get_part_numbers(matrix).iter().map(|&x| x as u64).sum()
 }
 
-fn check_gear_ratio(matrix: &Matrix, gear_coordinates: &(usize,usize)) -> Option<u32> {
-    let mut adjacent_nums:Vec<u32> = Vec::new();
+fn get_all_gear_ratios(matrix: &Matrix) -> u64 {
+    let mut gear_ratios: Vec<u64> = Vec::new();
 
-    for num in matrix.numbers.iter() {
-        if num.perimeter.contains(&gear_coordinates) {
-            adjacent_nums.push(num.number());
+    for gear_coordinates in matrix.gear_coordinates() {
+        if let Some(gear_ratio) = check_gear_ratio(matrix, &gear_coordinates) {
+            gear_ratios.push(gear_ratio);
         }
     }
 
-    // return Some(gear_ratio) if bordered by exactly two part numbers
-    if adjacent_nums.len() != 2 {
-        None
-    } else {
-        Some(adjacent_nums[0]*adjacent_nums[1])
-    }
+    gear_ratios.iter().sum::<u64>()
+}
+
+fn check_gear_ratio(matrix: &Matrix, gear_coordinates: &(usize, usize)) -> Option<u64> {
+    let (i, j) = *gear_coordinates;
+
+    let adjacent_nums: Vec<u32> = matrix
+        .adjacent_numbers(i, j)
+        .into_iter()
+        .map(|id| matrix.numbers[id].number())
+        .collect();
+
+    // return Some(gear_ratio) if bordered by the number of part numbers the rules ask for
+    matrix.rules.gear_ratio(&adjacent_nums)
 }
 
 #[derive(Clone, Debug)]
 struct Number {
-    data: Vec<char>,
-    coordinates: HashSet<(usize, usize)>,
-    perimeter: HashSet<(usize, usize)>,
+    // a number always sits on a single row, spanning columns start..start + len
+    row: usize,
+    start: usize,
+    len: usize,
+    // read once as the digits come in, None when they don't fit in a u32
+    value: Option<u32>,
 }
 
 impl Number {
-    fn new() -> Self {
+    fn new(row: usize, start: usize) -> Self {
         Self {
-            data: Vec::new(),
-            coordinates: HashSet::new(),
-            perimeter: HashSet::new(),
+            row,
+            start,
+            len: 0,
+            value: Some(0),
         }
     }
 
-    fn reset(&mut self) {
-        self.data.clear();
-        self.coordinates.clear();
-        self.perimeter.clear();
+    fn push_digit(&mut self, c: char) {
+        self.len += 1;
+        self.value = self
+            .value
+            .and_then(|x| x.checked_mul(10)?.checked_add(c.to_digit(10)?));
     }
 
     fn number(&self) -> u32 {
-        let data_string: String = self.data.iter().collect();
-        data_string.parse::<u32>().unwrap()
+        self.value.unwrap()
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.len
     }
 
-    fn derive_perimeter(&mut self, i_last_index: usize, j_last_index: usize) {
-        let mut perimeter: HashSet<(usize, usize)> = HashSet::new();
+    fn coordinates(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.start..self.end()).map(|j| (self.row, j))
+    }
 
-        // iterate through each digit, taking it's individual coordinates
-        for (i, j) in &self.coordinates {
-            let i_range = (i.saturating_sub(1))..=(usize::min(i + 1, i_last_index));
-            let j_range = (j.saturating_sub(1))..=(usize::min(j + 1, j_last_index));
+    fn contains(&self, coordinates: &(usize, usize)) -> bool {
+        coordinates.0 == self.row && (self.start..self.end()).contains(&coordinates.1)
+    }
 
-            // itertools macro to create an iterator cycling through all (i,j) tuples
-            iproduct!(i_range, j_range).for_each(|x| {
-                // don't insert if it's already a self coordinate
-                if !self.coordinates.contains(&x) {
-                    perimeter.insert(x);
-                }
-            })
-        }
-        self.perimeter = perimeter;
+    // bordering cells are generated on demand rather than stored per number,
+    // a cell touching several digits comes up once for each of them
+    fn perimeter(
+        &self,
+        neighbourhood: Neighbourhood,
+        height: usize,
+        width: usize,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.coordinates()
+            .flat_map(move |(i, j)| neighbourhood.around(i, j, height, width))
+            // skip the number's own coordinates
+            .filter(|x| !self.contains(x))
     }
 }
 
+// number_ids entry of a cell no number covers
+const NO_NUMBER: u32 = u32::MAX;
+
 #[derive(Clone, Debug)]
 struct Matrix {
     data: Vec<Vec<char>>,
     numbers: Vec<Number>,
-    symbol_coordinates: HashSet<(usize, usize)>,
+    // index into `numbers` for every cell covered by a digit, NO_NUMBER elsewhere, row after
+    // row of `width` cells in one buffer, four bytes a cell
+    number_ids: Vec<u32>,
+    // lines may be ragged, the widest one sets the width
+    width: usize,
+    rules: Rules,
 }
 
 impl Matrix {
@@ -101,114 +342,262 @@ impl Matrix {
         Matrix {
             data: Vec::new(),
             numbers: Vec::new(),
-            symbol_coordinates: HashSet::new(),
+            number_ids: Vec::new(),
+            width: 0,
+            rules: Rules::default(),
         }
     }
 
     // convenience constructor to fully populate each field from local file
-    fn build_from_file(filepath: &str) -> Matrix {
+    fn build_from_file(filepath: &str, rules: Rules) -> Result<Matrix, MatrixError> {
+        let file = fs::File::open(filepath)?;
+        Matrix::from_reader(file, rules)
+    }
+
+    fn from_reader<R: Read>(mut reader: R, rules: Rules) -> Result<Matrix, MatrixError> {
+        let mut input_string = String::new();
+        reader.read_to_string(&mut input_string)?;
+
+        Matrix::parse(&input_string, rules)
+    }
+
+    fn parse(s: &str, rules: Rules) -> Result<Matrix, MatrixError> {
         let mut matrix = Matrix::new();
+        matrix.rules = rules;
 
         // populate data
-        matrix.populate_data_from_file(filepath);
+        matrix.populate_data_from_str(s);
 
-        // populate symbols
-        matrix.populate_gear_coordinates();
+        matrix.populate_from_data();
 
-        // populate numbers
-        matrix.populate_numbers();
+        // every number has to fit the u32 that number() reads it into
+        if let Some(num) = matrix.numbers.iter().find(|num| num.value.is_none()) {
+            return Err(MatrixError::NumberTooLarge {
+                row: num.row,
+                column: num.start,
+            });
+        }
 
-        matrix
+        // and every gear ratio the u64 that gear_ratio() works it out in
+        if let Some((row, column)) = matrix
+            .gear_coordinates()
+            .find(|&(i, j)| !matrix.gear_ratio_fits(i, j))
+        {
+            return Err(MatrixError::GearRatioTooLarge { row, column });
+        }
+
+        Ok(matrix)
     }
 
-    fn populate_data_from_file(&mut self, filepath: &str) {
-        let input_string = fs::read_to_string(filepath).expect("unable to read data from file");
+    // everything derived from data, symbols and gears are read straight from it
+    fn populate_from_data(&mut self) {
+        self.populate_numbers();
+    }
 
+    fn populate_data_from_str(&mut self, input_string: &str) {
         let data = input_string
             .lines()
             .map(|line| line.chars().collect::<Vec<char>>())
             .collect::<Vec<Vec<char>>>();
 
+        self.populate_data(data);
+    }
+
+    fn populate_data(&mut self, data: Vec<Vec<char>>) {
+        self.width = data.iter().map(|line| line.len()).max().unwrap_or(0);
         self.data = data;
     }
 
-    fn populate_gear_coordinates(&mut self) {
-        let mut result: HashSet<(usize, usize)> = HashSet::new();
+    fn populate_numbers(&mut self) {
+        let mut all_numbers: Vec<Number> = Vec::new();
 
         for (i, line) in self.data.iter().enumerate() {
-            for (j, c) in line.iter().enumerate() {
-                if c == &'*' {
-                    result.insert((i, j));
-                }
+            all_numbers.extend(Matrix::get_numbers_in_line(i, line))
+        }
+
+        // stamp each number's id onto the cells it covers
+        self.number_ids = vec![NO_NUMBER; self.height() * self.width()];
+
+        for (id, num) in all_numbers.iter().enumerate() {
+            for (i, j) in num.coordinates() {
+                self.set_number_at(i, j, Some(id));
             }
         }
-        self.symbol_coordinates = result;
+
+        self.numbers = all_numbers;
     }
 
-    fn populate_numbers(&mut self) {
-        let mut all_numbers: Vec<Number> = Vec::new();
+    fn height(&self) -> usize {
+        self.data.len()
+    }
 
-        for (i, line) in self.data.iter().enumerate() {
-            all_numbers.extend(Matrix::get_numbers_in_line(i, line).into_iter())
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    // cells past the end of a short line don't exist and read as None
+    fn get(&self, i: usize, j: usize) -> Option<char> {
+        self.data.get(i)?.get(j).copied()
+    }
+
+    // cells touching (i, j) under the rules' neighbourhood, skipping the gaps after short lines
+    fn neighbours(&self, i: usize, j: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.rules
+            .neighbourhood
+            .around(i, j, self.height(), self.width())
+            .filter(|&(x, y)| self.get(x, y).is_some())
+    }
+
+    // cells past the end of a short line are neither symbols nor gears
+    fn is_symbol(&self, i: usize, j: usize) -> bool {
+        self.get(i, j).is_some_and(|c| self.rules.is_symbol(c))
+    }
+
+    fn is_gear(&self, i: usize, j: usize) -> bool {
+        self.get(i, j).is_some_and(|c| self.rules.is_gear(c))
+    }
+
+    // every symbol, in reading order
+    fn symbol_coordinates(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cells().filter(|&(i, j)| self.is_symbol(i, j))
+    }
+
+    // every gear, in reading order
+    fn gear_coordinates(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cells().filter(|&(i, j)| self.is_gear(i, j))
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| (0..line.len()).map(move |j| (i, j)))
+    }
+
+    // id of the number covering (i, j), if any
+    fn number_at(&self, i: usize, j: usize) -> Option<usize> {
+        if j >= self.width {
+            return None;
         }
+        match *self.number_ids.get(i * self.width + j)? {
+            NO_NUMBER => None,
+            id => Some(id as usize),
+        }
+    }
 
-        // matrix dimensions
-        let j_last_index = &self.data.len() - 1;
-        let i_last_index = &self.data[0].len() - 1;
+    fn set_number_at(&mut self, i: usize, j: usize, id: Option<usize>) {
+        self.number_ids[i * self.width + j] = match id {
+            Some(id) => u32::try_from(id)
+                .ok()
+                .filter(|&id| id != NO_NUMBER)
+                .expect("more numbers than a u32 id can tell apart"),
+            None => NO_NUMBER,
+        };
+    }
 
-        // populate perimeter data for each number
-        all_numbers
-            .iter_mut()
-            .for_each(|x| x.derive_perimeter(i_last_index, j_last_index));
+    fn gear_ratio_fits(&self, i: usize, j: usize) -> bool {
+        let nums: Vec<u32> = self
+            .adjacent_numbers(i, j)
+            .into_iter()
+            .map(|id| self.numbers[id].number())
+            .collect();
 
-        self.numbers = all_numbers;
+        self.rules.ratio_fits(&nums)
+    }
+
+    // each number only looks at the cells bordering it, so this is linear in grid size
+    fn is_part_number(&self, num: &Number) -> bool {
+        num.perimeter(self.rules.neighbourhood, self.height(), self.width())
+            .any(|(x, y)| self.is_symbol(x, y))
+    }
+
+    // ids of the distinct numbers touching a cell
+    fn adjacent_numbers(&self, i: usize, j: usize) -> Vec<usize> {
+        let mut result: Vec<usize> = Vec::new();
+
+        for (x, y) in self.neighbours(i, j) {
+            if let Some(id) = self.number_at(x, y) {
+                // only a handful of neighbours, a linear scan is cheaper than hashing
+                if !result.contains(&id) {
+                    result.push(id);
+                }
+            }
+        }
+
+        result
     }
 
     //Example input: "467..114.." and ".#.35..633"
-    fn get_numbers_in_line(line_number: usize, line: &Vec<char>) -> Vec<Number> {
-        let mut current_number: Number = Number::new();
+    fn get_numbers_in_line(line_number: usize, line: &[char]) -> Vec<Number> {
+        let mut current_number: Option<Number> = None;
         let mut all_numbers: Vec<Number> = Vec::new();
-        let mut collecting: bool = false;
 
         for (j, &c) in line.iter().enumerate() {
-            if c.is_numeric() {
-                // start collecting
-                if !collecting {
-                    collecting = true;
-                }
-                // update current number data
-                current_number.data.push(c);
-                match current_number.coordinates.insert((line_number, j)) {
-                    true => {}
-                    false => {
-                        panic!()
-                    }
-                }
-            } else {
-                if collecting {
-                    // a number was being formed but is now completed
-                    collecting = false;
-                    all_numbers.push(current_number.clone());
-                    current_number.reset();
-                }
+            if c.is_ascii_digit() {
+                // start collecting if no number is being formed, then update its data
+                current_number
+                    .get_or_insert_with(|| Number::new(line_number, j))
+                    .push_digit(c);
+            } else if let Some(number) = current_number.take() {
+                // a number was being formed but is now completed
+                all_numbers.push(number);
             }
         }
 
         //special case, last character in line is numeric
-        if collecting {
-            all_numbers.push(current_number.clone());
-        }
+        all_numbers.extend(current_number);
 
         all_numbers
     }
 }
 
+impl FromStr for Matrix {
+    type Err = MatrixError;
+
+    // a schematic under the puzzle's rules
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Matrix::parse(s, Rules::default())
+    }
+}
+
+#[derive(Debug)]
+enum MatrixError {
+    Io(io::Error),
+    NumberTooLarge { row: usize, column: usize },
+    GearRatioTooLarge { row: usize, column: usize },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatrixError::Io(e) => write!(f, "unable to read schematic: {}", e),
+            MatrixError::NumberTooLarge { row, column } => {
+                write!(f, "number at {},{} is too large", row, column)
+            }
+            MatrixError::GearRatioTooLarge { row, column } => {
+                write!(f, "gear ratio at {},{} is too large", row, column)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+impl From<io::Error> for MatrixError {
+    fn from(e: io::Error) -> Self {
+        MatrixError::Io(e)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    const TEST_DATA: &str = include_str!("../test_data.txt");
 
     #[test]
-    fn test_matrix_data_from_file() {
+    fn test_matrix_data() {
         let expected_data = vec![
             vec!['4', '6', '7', '.', '.', '1', '1', '4', '.', '.'],
             vec!['.', '.', '.', '*', '.', '.', '.', '.', '.', '.'],
@@ -222,10 +611,55 @@ mod tests {
             vec!['.', '6', '6', '4', '.', '5', '9', '8', '.', '.'],
         ];
 
-        let matrix = Matrix::build_from_file("test_data.txt");
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
         assert_eq!(matrix.data, expected_data);
     }
 
+    #[test]
+    fn test_matrix_from_str() {
+        let matrix: Matrix = TEST_DATA.parse().unwrap();
+
+        assert_eq!(matrix.numbers.len(), 10);
+        assert_eq!(get_all_gear_ratios(&matrix), 467835);
+    }
+
+    #[test]
+    fn test_matrix_from_reader() {
+        let matrix = Matrix::from_reader(TEST_DATA.as_bytes(), Rules::default()).unwrap();
+        assert_eq!(get_part_numbers(&matrix).iter().sum::<u32>(), 4361);
+
+        // not utf-8
+        let err = Matrix::from_reader(&[0xff, 0xfe][..], Rules::default()).unwrap_err();
+        assert!(matches!(err, MatrixError::Io(_)));
+    }
+
+    #[test]
+    fn test_matrix_number_too_large() {
+        let err = "..*.\n.12345678901".parse::<Matrix>().unwrap_err();
+
+        assert!(matches!(
+            err,
+            MatrixError::NumberTooLarge { row: 1, column: 1 }
+        ));
+        assert_eq!(err.to_string(), "number at 1,1 is too large");
+    }
+
+    #[test]
+    fn test_matrix_gear_ratio_too_large() {
+        let input = "999999999.999999999\n.........*.........\n999999999..........\n";
+        let rules = "neighbours = at least 3".parse::<Rules>().unwrap();
+
+        let err = Matrix::parse(input, rules).unwrap_err();
+        assert!(matches!(
+            err,
+            MatrixError::GearRatioTooLarge { row: 1, column: 9 }
+        ));
+        assert_eq!(err.to_string(), "gear ratio at 1,9 is too large");
+
+        // under the puzzle's rules three numbers don't make a gear at all
+        assert_eq!(get_all_gear_ratios(&input.parse().unwrap()), 0);
+    }
+
     #[test]
     fn test_matrix_get_numbers_in_line_type1() {
         let line_number = 0;
@@ -234,18 +668,18 @@ mod tests {
 
         assert_eq!(numbers.len(), 2);
 
-        assert_eq!(numbers[0].data, vec!['4', '6', '7']);
+        assert_eq!(numbers[0].value, Some(467));
         assert_eq!(
-            numbers[0].coordinates,
+            numbers[0].coordinates().collect::<HashSet<_>>(),
             [(0, 0), (0, 1), (0, 2)]
                 .iter()
                 .cloned()
                 .collect::<HashSet<_>>()
         );
 
-        assert_eq!(numbers[1].data, vec!['1', '1', '4']);
+        assert_eq!(numbers[1].value, Some(114));
         assert_eq!(
-            numbers[1].coordinates,
+            numbers[1].coordinates().collect::<HashSet<_>>(),
             [(0, 5), (0, 6), (0, 7)]
                 .iter()
                 .cloned()
@@ -261,15 +695,15 @@ mod tests {
 
         assert_eq!(numbers.len(), 2);
 
-        assert_eq!(numbers[0].data, vec!['3', '5']);
+        assert_eq!(numbers[0].value, Some(35));
         assert_eq!(
-            numbers[0].coordinates,
+            numbers[0].coordinates().collect::<HashSet<_>>(),
             [(0, 3), (0, 4)].iter().cloned().collect::<HashSet<_>>()
         );
 
-        assert_eq!(numbers[1].data, vec!['6', '3', '3']);
+        assert_eq!(numbers[1].value, Some(633));
         assert_eq!(
-            numbers[1].coordinates,
+            numbers[1].coordinates().collect::<HashSet<_>>(),
             [(0, 7), (0, 8), (0, 9)]
                 .iter()
                 .cloned()
@@ -280,24 +714,24 @@ mod tests {
     #[test]
     fn test_matrix_populate_numbers() {
         let mut matrix = Matrix::new();
-        matrix.populate_data_from_file("test_data.txt");
+        matrix.populate_data_from_str(TEST_DATA);
 
         matrix.populate_numbers();
 
         assert_eq!(matrix.numbers.len(), 10);
 
-        assert_eq!(matrix.numbers[0].data, vec!['4', '6', '7']);
+        assert_eq!(matrix.numbers[0].value, Some(467));
         assert_eq!(
-            matrix.numbers[0].coordinates,
+            matrix.numbers[0].coordinates().collect::<HashSet<_>>(),
             [(0, 0), (0, 1), (0, 2)]
                 .iter()
                 .cloned()
                 .collect::<HashSet<_>>()
         );
 
-        assert_eq!(matrix.numbers[9].data, vec!['5', '9', '8']);
+        assert_eq!(matrix.numbers[9].value, Some(598));
         assert_eq!(
-            matrix.numbers[9].coordinates,
+            matrix.numbers[9].coordinates().collect::<HashSet<_>>(),
             [(9, 5), (9, 6), (9, 7)]
                 .iter()
                 .cloned()
@@ -306,63 +740,379 @@ mod tests {
     }
 
     #[test]
-    fn test_matrix_populate_gear_coordinates() {
+    fn test_matrix_populate_number_ids() {
         let mut matrix = Matrix::new();
-        matrix.populate_data_from_file("test_data.txt");
+        matrix.populate_data_from_str(TEST_DATA);
 
-        matrix.populate_gear_coordinates();
+        matrix.populate_numbers();
 
-        assert_eq!(matrix.symbol_coordinates.len(), 3);
+        let row = |i: usize, columns: std::ops::Range<usize>| -> Vec<Option<usize>> {
+            columns.map(|j| matrix.number_at(i, j)).collect()
+        };
 
-        let expected = [(1,3),(4,3),(8,5)]
-            .iter()
-            .cloned()
-            .collect::<HashSet<_>>();
-        assert_eq!(matrix.symbol_coordinates, expected);
+        assert_eq!(row(0, 0..4), [Some(0), Some(0), Some(0), None]);
+        assert_eq!(row(9, 5..8), [Some(9), Some(9), Some(9)]);
+        assert_eq!(row(1, 0..10), vec![None; 10]);
+        assert_eq!(matrix.number_ids.len(), 100);
     }
 
     #[test]
-    fn test_number_data_to_number() {
-        let number: Number = Number {
-            data: vec!['4', '6', '7'],
-            coordinates: HashSet::new(),
-            perimeter: HashSet::new(),
-        };
-        assert_eq!(number.number(), 467);
+    fn test_matrix_symbol_coordinates() {
+        let mut matrix = Matrix::new();
+        matrix.populate_data_from_str(TEST_DATA);
+
+        assert_eq!(
+            matrix.symbol_coordinates().collect::<Vec<_>>(),
+            vec![(1, 3), (3, 6), (4, 3), (5, 5), (8, 3), (8, 5)]
+        );
+        assert!(matrix.is_symbol(3, 6));
+        assert!(!matrix.is_symbol(0, 0));
+        assert!(!matrix.is_symbol(10, 0));
+    }
+
+    #[test]
+    fn test_matrix_gear_coordinates() {
+        let mut matrix = Matrix::new();
+        matrix.populate_data_from_str(TEST_DATA);
+
+        assert_eq!(
+            matrix.gear_coordinates().collect::<Vec<_>>(),
+            vec![(1, 3), (4, 3), (8, 5)]
+        );
+        assert!(!matrix.is_gear(3, 6));
+    }
+
+    #[test]
+    fn test_matrix_adjacent_numbers() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+
+        // 467 and 35 around the first gear, 617 alone around the second
+        assert_eq!(matrix.adjacent_numbers(1, 3), vec![0, 2]);
+        assert_eq!(matrix.adjacent_numbers(4, 3), vec![4]);
+        assert_eq!(matrix.adjacent_numbers(0, 8), vec![1]);
+    }
+
+    #[test]
+    fn test_get_part_numbers() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+
+        assert_eq!(get_part_numbers(&matrix).iter().sum::<u32>(), 4361);
+    }
+
+    #[test]
+    fn test_part_number_sum_past_u32() {
+        let line = ["999999999"; 10].join("#");
+        let matrix: Matrix = line.parse().unwrap();
+
+        assert_eq!(part_number_sum(&matrix), 9_999_999_990);
+    }
+
+    #[test]
+    fn test_get_all_gear_ratios() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+
+        assert_eq!(get_all_gear_ratios(&matrix), 467835);
+    }
+
+    #[test]
+    fn test_matrix_with_rules() {
+        let rules = "symbols = #$\ngears = *+\nneighbours = at least 1\ncombine = sum"
+            .parse::<Rules>()
+            .unwrap();
+        let matrix = Matrix::parse(TEST_DATA, rules).unwrap();
+
+        // 467, 35, 617 and 598 only touch '*' or '+', which no longer count as symbols
+        assert_eq!(get_part_numbers(&matrix), vec![633, 664]);
+        assert_eq!(matrix.gear_coordinates().count(), 4);
+        assert_eq!(
+            get_all_gear_ratios(&matrix),
+            (467 + 35) + 617 + 592 + (755 + 598)
+        );
+    }
+
+    #[test]
+    fn test_matrix_dimensions() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+        assert_eq!((matrix.height(), matrix.width()), (10, 10));
+
+        let matrix =
+            Matrix::parse(include_str!("../test_data_wide.txt"), Rules::default()).unwrap();
+        assert_eq!((matrix.height(), matrix.width()), (3, 12));
+
+        let matrix =
+            Matrix::parse(include_str!("../test_data_tall.txt"), Rules::default()).unwrap();
+        assert_eq!((matrix.height(), matrix.width()), (12, 3));
+
+        let matrix =
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap();
+        assert_eq!((matrix.height(), matrix.width()), (5, 11));
+    }
+
+    #[test]
+    fn test_matrix_get_ragged() {
+        let matrix =
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap();
+
+        assert_eq!(matrix.get(2, 10), Some('#'));
+        assert_eq!(matrix.get(0, 8), None);
+        assert_eq!(matrix.get(4, 4), None);
+        assert_eq!(matrix.get(5, 0), None);
+    }
+
+    #[test]
+    fn test_non_square_matrix() {
+        // numbers well past the last row index, and rows well past the last column index
+        let matrix =
+            Matrix::parse(include_str!("../test_data_wide.txt"), Rules::default()).unwrap();
+        assert_eq!(get_part_numbers(&matrix), vec![12, 4, 8, 56]);
+        assert_eq!(get_all_gear_ratios(&matrix), 32);
+
+        let matrix =
+            Matrix::parse(include_str!("../test_data_tall.txt"), Rules::default()).unwrap();
+        assert_eq!(get_part_numbers(&matrix), vec![1, 2, 3, 4]);
+        assert_eq!(get_all_gear_ratios(&matrix), 2);
+    }
+
+    #[test]
+    fn test_ragged_matrix() {
+        let matrix =
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap();
+
+        assert_eq!(get_part_numbers(&matrix), vec![467, 35, 633, 617]);
+        assert_eq!(get_all_gear_ratios(&matrix), 16345);
+    }
+
+    #[test]
+    fn test_empty_matrix() {
+        let mut matrix = Matrix::new();
+        matrix.populate_numbers();
+
+        assert_eq!((matrix.height(), matrix.width()), (0, 0));
+        assert!(get_part_numbers(&matrix).is_empty());
+    }
+
+    #[test]
+    fn test_matrix_number_at() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+
+        assert_eq!(matrix.number_at(0, 2), Some(0));
+        assert_eq!(matrix.number_at(9, 7), Some(9));
+        assert_eq!(matrix.number_at(0, 3), None);
+        assert_eq!(matrix.number_at(10, 0), None);
+    }
+
+    #[test]
+    fn test_parse_cell() {
+        assert_eq!(parse_cell("0,2"), Ok((0, 2)));
+        assert_eq!(parse_cell(" 12 , 3"), Ok((12, 3)));
+        assert!(parse_cell("12").is_err());
+        assert!(parse_cell("a,3").is_err());
+    }
+
+    #[test]
+    fn test_parse_edit() {
+        assert_eq!(parse_edit("1,3=#"), Ok((1, 3, '#')));
+        assert_eq!(parse_edit("0,0=="), Ok((0, 0, '=')));
+        assert!(parse_edit("1,3").is_err());
+        assert!(parse_edit("1,3=ab").is_err());
+        assert!(parse_edit("1,3=").is_err());
+    }
+
+    #[test]
+    fn test_number_push_digit() {
+        let mut number = Number::new(0, 0);
+        for c in ['4', '6', '7'] {
+            number.push_digit(c);
+        }
+        assert_eq!((number.len, number.number()), (3, 467));
+
+        // past u32::MAX the value is gone for good, however many digits follow
+        let mut number = Number::new(0, 0);
+        for c in "42949672950".chars() {
+            number.push_digit(c);
+        }
+        assert_eq!((number.len, number.value), (11, None));
     }
 
     #[test]
     fn derive_perimeter_limit_case() {
-        let mut number = Number {
-            data: Vec::new(),
-            coordinates: vec![(1, 1), (1, 2)].into_iter().collect(),
-            perimeter: HashSet::new(),
+        let number = Number {
+            row: 1,
+            start: 1,
+            len: 2,
+            value: Some(12),
         };
 
-        number.derive_perimeter(3, 2);
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::Moore, 4, 3).collect();
 
         let expected_perimeter: HashSet<_> =
             vec![(0, 0), (0, 1), (0, 2), (1, 0), (2, 0), (2, 1), (2, 2)]
                 .into_iter()
                 .collect();
 
-        assert_eq!(number.perimeter, expected_perimeter);
+        assert_eq!(perimeter, expected_perimeter);
     }
 
     #[test]
     fn derive_perimeter_edge_case() {
-        let mut number = Number {
-            data: Vec::new(),
-            coordinates: vec![(1, 0), (2, 0)].into_iter().collect(),
-            perimeter: HashSet::new(),
+        let number = Number {
+            row: 0,
+            start: 0,
+            len: 2,
+            value: Some(12),
+        };
+
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::Moore, 4, 4).collect();
+
+        let expected_perimeter: HashSet<_> =
+            vec![(0, 2), (1, 0), (1, 1), (1, 2)].into_iter().collect();
+
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn derive_perimeter_von_neumann_limit_case() {
+        let number = Number {
+            row: 1,
+            start: 1,
+            len: 2,
+            value: Some(12),
+        };
+
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::VonNeumann, 4, 3).collect();
+
+        let expected_perimeter: HashSet<_> = vec![(0, 1), (0, 2), (1, 0), (2, 1), (2, 2)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn derive_perimeter_von_neumann_edge_case() {
+        let number = Number {
+            row: 0,
+            start: 0,
+            len: 2,
+            value: Some(12),
+        };
+
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::VonNeumann, 4, 4).collect();
+
+        let expected_perimeter: HashSet<_> = vec![(0, 2), (1, 0), (1, 1)].into_iter().collect();
+
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn derive_perimeter_moore_radius_limit_case() {
+        let number = Number {
+            row: 1,
+            start: 1,
+            len: 2,
+            value: Some(12),
+        };
+
+        // the whole 4 x 3 grid apart from the number itself
+        let perimeter: HashSet<_> = number
+            .perimeter(Neighbourhood::MooreRadius(2), 4, 3)
+            .collect();
+
+        assert_eq!(perimeter.len(), 10);
+        assert!(perimeter.contains(&(3, 0)));
+        assert!(!perimeter.contains(&(1, 1)));
+    }
+
+    #[test]
+    fn derive_perimeter_moore_radius_edge_case() {
+        let number = Number {
+            row: 0,
+            start: 0,
+            len: 2,
+            value: Some(12),
+        };
+
+        let perimeter: HashSet<_> = number
+            .perimeter(Neighbourhood::MooreRadius(2), 5, 6)
+            .collect();
+
+        let expected_perimeter: HashSet<_> = (0..=2)
+            .flat_map(|i| (0..=3).map(move |j| (i, j)))
+            .filter(|&x| x != (0, 0) && x != (0, 1))
+            .collect();
+
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn derive_perimeter_toroidal_limit_case() {
+        // spans the full width, so wrapping along the row lands back on the number
+        let number = Number {
+            row: 1,
+            start: 0,
+            len: 3,
+            value: Some(123),
         };
 
-        number.derive_perimeter(3, 3);
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::Toroidal, 3, 3).collect();
 
-        let expected_perimeter: HashSet<_> = vec![(0, 0), (0, 1), (1, 1), (2, 1), (3, 0), (3, 1)]
+        let expected_perimeter: HashSet<_> = vec![(0, 0), (0, 1), (0, 2), (2, 0), (2, 1), (2, 2)]
             .into_iter()
             .collect();
 
-        assert_eq!(number.perimeter, expected_perimeter);
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn derive_perimeter_toroidal_edge_case() {
+        let number = Number {
+            row: 0,
+            start: 0,
+            len: 2,
+            value: Some(12),
+        };
+
+        let perimeter: HashSet<_> = number.perimeter(Neighbourhood::Toroidal, 4, 5).collect();
+
+        let expected_perimeter: HashSet<_> = vec![
+            (3, 4),
+            (3, 0),
+            (3, 1),
+            (3, 2),
+            (0, 4),
+            (0, 2),
+            (1, 4),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(perimeter, expected_perimeter);
+    }
+
+    #[test]
+    fn test_matrix_neighbourhoods() {
+        let build = |neighbourhood: Neighbourhood| {
+            let rules = Rules {
+                neighbourhood,
+                ..Rules::default()
+            };
+            Matrix::parse(TEST_DATA, rules).unwrap()
+        };
+
+        // diagonal contact no longer counts, which drops 467 and 592
+        let matrix = build(Neighbourhood::VonNeumann);
+        assert_eq!(get_part_numbers(&matrix), vec![35, 633, 617, 664, 598]);
+        assert_eq!(get_all_gear_ratios(&matrix), 0);
+
+        let matrix = build(Neighbourhood::Toroidal);
+        assert_eq!(get_part_numbers(&matrix).iter().sum::<u32>(), 4361);
+
+        // 114 and 58 are within two cells of a symbol
+        let matrix = build(Neighbourhood::MooreRadius(2));
+        assert!(get_part_numbers(&matrix).contains(&114));
+        assert!(get_part_numbers(&matrix).contains(&58));
     }
 }
@@ -0,0 +1,210 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+// which cells count as touching a given cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Neighbourhood {
+    // the 4 orthogonal cells
+    VonNeumann,
+    // the 8 surrounding cells, the puzzle's definition
+    Moore,
+    // every cell within r rows and r columns
+    MooreRadius(usize),
+    // the 8 surrounding cells, wrapping round the edges of the grid
+    Toroidal,
+}
+
+impl Neighbourhood {
+    fn radius(&self) -> usize {
+        match *self {
+            Neighbourhood::MooreRadius(r) => r,
+            _ => 1,
+        }
+    }
+
+    fn wraps(&self) -> bool {
+        *self == Neighbourhood::Toroidal
+    }
+
+    // (di, dj) steps from a cell to each of its neighbours, within rows and columns of steps
+    fn offsets(
+        &self,
+        rows: RangeInclusive<isize>,
+        columns: RangeInclusive<isize>,
+    ) -> impl Iterator<Item = (isize, isize)> {
+        let orthogonal_only = *self == Neighbourhood::VonNeumann;
+
+        rows.flat_map(move |di| columns.clone().map(move |dj| (di, dj)))
+            .filter(|&offset| offset != (0, 0))
+            .filter(move |(di, dj)| !orthogonal_only || di.abs() + dj.abs() == 1)
+    }
+
+    // neighbours of (i, j) inside a height x width grid, either clipped or wrapped at the edges
+    // a wrapped neighbourhood on a very small grid can reach the same cell twice
+    pub fn around(
+        &self,
+        i: usize,
+        j: usize,
+        height: usize,
+        width: usize,
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let wraps = self.wraps();
+        // a clipped neighbourhood only walks the steps that stay inside the grid, so moore:1000000
+        // costs no more than the whole grid
+        let r = self.radius().min(height.max(width)) as isize;
+        let within = |x: usize, len: usize| match wraps {
+            true => -r..=r,
+            false => (-r).max(-(x as isize))..=r.min(len as isize - 1 - x as isize),
+        };
+
+        self.offsets(within(i, height), within(j, width))
+            .filter_map(move |(di, dj)| {
+                Some((step(i, di, height, wraps)?, step(j, dj, width, wraps)?))
+            })
+            .filter(move |&x| x != (i, j))
+    }
+}
+
+fn step(x: usize, dx: isize, len: usize, wraps: bool) -> Option<usize> {
+    if wraps {
+        if len == 0 {
+            return None;
+        }
+        Some((x as isize + dx).rem_euclid(len as isize) as usize)
+    } else {
+        x.checked_add_signed(dx).filter(|&y| y < len)
+    }
+}
+
+impl fmt::Display for Neighbourhood {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Neighbourhood::VonNeumann => write!(f, "von-neumann"),
+            Neighbourhood::Moore => write!(f, "moore"),
+            Neighbourhood::MooreRadius(r) => write!(f, "moore:{}", r),
+            Neighbourhood::Toroidal => write!(f, "toroidal"),
+        }
+    }
+}
+
+// Example input: "von-neumann", "moore", "moore:2" or "toroidal"
+impl FromStr for Neighbourhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "von-neumann" => Ok(Neighbourhood::VonNeumann),
+            "moore" => Ok(Neighbourhood::Moore),
+            "toroidal" => Ok(Neighbourhood::Toroidal),
+            other => {
+                let r = other
+                    .strip_prefix("moore:")
+                    .and_then(|r| r.parse::<usize>().ok())
+                    .filter(|&r| r > 0)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown neighbourhood `{}`, expected von-neumann, moore, moore:R or toroidal",
+                            other
+                        )
+                    })?;
+                Ok(Neighbourhood::MooreRadius(r))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn around(
+        n: Neighbourhood,
+        i: usize,
+        j: usize,
+        height: usize,
+        width: usize,
+    ) -> HashSet<(usize, usize)> {
+        n.around(i, j, height, width).collect()
+    }
+
+    #[test]
+    fn test_von_neumann() {
+        let expected: HashSet<_> = vec![(0, 1), (1, 0), (1, 2), (2, 1)].into_iter().collect();
+        assert_eq!(around(Neighbourhood::VonNeumann, 1, 1, 3, 3), expected);
+
+        // corner
+        let expected: HashSet<_> = vec![(0, 1), (1, 0)].into_iter().collect();
+        assert_eq!(around(Neighbourhood::VonNeumann, 0, 0, 3, 3), expected);
+    }
+
+    #[test]
+    fn test_moore() {
+        assert_eq!(Neighbourhood::Moore.around(1, 1, 3, 3).count(), 8);
+
+        // bottom right corner of a non-square grid
+        let expected: HashSet<_> = vec![(0, 3), (0, 4), (1, 3)].into_iter().collect();
+        assert_eq!(around(Neighbourhood::Moore, 1, 4, 2, 5), expected);
+    }
+
+    #[test]
+    fn test_moore_radius() {
+        assert_eq!(Neighbourhood::MooreRadius(2).around(2, 2, 5, 5).count(), 24);
+
+        // clipped to the 3 x 3 block left of the corner
+        assert_eq!(Neighbourhood::MooreRadius(2).around(0, 0, 5, 5).count(), 8);
+
+        // a huge radius covers the whole grid without walking every offset
+        assert_eq!(
+            Neighbourhood::MooreRadius(1_000_000)
+                .around(1, 2, 3, 4)
+                .count(),
+            11
+        );
+        assert_eq!(
+            Neighbourhood::MooreRadius(usize::MAX)
+                .around(0, 0, 2, 2)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_toroidal() {
+        let expected: HashSet<_> = vec![
+            (2, 4),
+            (2, 0),
+            (2, 1),
+            (0, 4),
+            (0, 1),
+            (1, 4),
+            (1, 0),
+            (1, 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(around(Neighbourhood::Toroidal, 0, 0, 3, 5), expected);
+
+        // a single row wraps onto itself, and never back onto the cell itself
+        let expected: HashSet<_> = vec![(0, 1), (0, 2)].into_iter().collect();
+        assert_eq!(around(Neighbourhood::Toroidal, 0, 0, 1, 3), expected);
+        assert!(around(Neighbourhood::Toroidal, 0, 0, 1, 1).is_empty());
+    }
+
+    #[test]
+    fn test_neighbourhood_from_str() {
+        for n in [
+            Neighbourhood::VonNeumann,
+            Neighbourhood::Moore,
+            Neighbourhood::MooreRadius(3),
+            Neighbourhood::Toroidal,
+        ] {
+            assert_eq!(n.to_string().parse::<Neighbourhood>(), Ok(n));
+        }
+
+        assert!("moore:0".parse::<Neighbourhood>().is_err());
+        assert!("moore:x".parse::<Neighbourhood>().is_err());
+        assert!("hexagonal".parse::<Neighbourhood>().is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{check_gear_ratio, Matrix};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+const CELL_SIZE: usize = 16;
+
+// the role a cell plays in the schematic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellKind {
+    Empty,
+    PartNumber,
+    OtherNumber,
+    Symbol,
+    // a gear with the neighbour count the rules ask for
+    Gear,
+    // a gear with too few or too many neighbouring numbers
+    BadGear,
+}
+
+impl CellKind {
+    const ALL: [CellKind; 6] = [
+        CellKind::Empty,
+        CellKind::PartNumber,
+        CellKind::OtherNumber,
+        CellKind::Symbol,
+        CellKind::Gear,
+        CellKind::BadGear,
+    ];
+
+    fn ansi(&self) -> &'static str {
+        match self {
+            CellKind::Empty => "2",
+            CellKind::PartNumber => "1;32",
+            CellKind::OtherNumber => "90",
+            CellKind::Symbol => "1;33",
+            CellKind::Gear => "1;36",
+            CellKind::BadGear => "1;31",
+        }
+    }
+
+    fn colour(&self) -> &'static str {
+        match self {
+            CellKind::Empty => "#ffffff",
+            CellKind::PartNumber => "#a6e3a1",
+            CellKind::OtherNumber => "#d0d0d0",
+            CellKind::Symbol => "#f9e2af",
+            CellKind::Gear => "#89dceb",
+            CellKind::BadGear => "#f38ba8",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            CellKind::Empty => "empty",
+            CellKind::PartNumber => "part number",
+            CellKind::OtherNumber => "other number",
+            CellKind::Symbol => "symbol",
+            CellKind::Gear => "gear",
+            CellKind::BadGear => "bad gear",
+        }
+    }
+}
+
+// a kind for every cell, laid out like the matrix data
+pub fn classify(matrix: &Matrix) -> Vec<Vec<CellKind>> {
+    let mut kinds: Vec<Vec<CellKind>> = matrix
+        .data
+        .iter()
+        .map(|line| vec![CellKind::Empty; line.len()])
+        .collect();
+
+    for num in &matrix.numbers {
+        let kind = match matrix.is_part_number(num) {
+            true => CellKind::PartNumber,
+            false => CellKind::OtherNumber,
+        };
+        for (i, j) in num.coordinates() {
+            kinds[i][j] = kind;
+        }
+    }
+
+    for (i, j) in matrix.symbol_coordinates() {
+        kinds[i][j] = CellKind::Symbol;
+    }
+
+    // gears are drawn as gears even when they are also symbols
+    for (i, j) in matrix.gear_coordinates() {
+        kinds[i][j] = match check_gear_ratio(matrix, &(i, j)) {
+            Some(_) => CellKind::Gear,
+            None => CellKind::BadGear,
+        };
+    }
+
+    kinds
+}
+
+// cells bordering the highlighted number, if there is one
+fn highlighted(matrix: &Matrix, highlight: Option<usize>) -> HashSet<(usize, usize)> {
+    match highlight {
+        Some(id) => matrix.numbers[id]
+            .perimeter(matrix.rules.neighbourhood, matrix.height(), matrix.width())
+            .collect(),
+        None => HashSet::new(),
+    }
+}
+
+pub fn ansi(matrix: &Matrix, highlight: Option<usize>) -> String {
+    let kinds = classify(matrix);
+    let highlighted = highlighted(matrix, highlight);
+    let mut result = String::new();
+
+    for (i, line) in matrix.data.iter().enumerate() {
+        for (j, c) in line.iter().enumerate() {
+            let background = match highlighted.contains(&(i, j)) {
+                true => ";44",
+                false => "",
+            };
+            write!(
+                result,
+                "\x1b[{}{}m{}\x1b[0m",
+                kinds[i][j].ansi(),
+                background,
+                c
+            )
+            .unwrap();
+        }
+        result.push('\n');
+    }
+
+    // legend
+    for kind in CellKind::ALL.iter().skip(1) {
+        write!(result, "\x1b[{}m{}\x1b[0m  ", kind.ansi(), kind.label()).unwrap();
+    }
+    if highlight.is_some() {
+        result.push_str("\x1b[44mperimeter\x1b[0m");
+    }
+    result.push('\n');
+
+    result
+}
+
+pub fn svg(matrix: &Matrix, highlight: Option<usize>) -> String {
+    let kinds = classify(matrix);
+    let highlighted = highlighted(matrix, highlight);
+    let (width, height) = (matrix.width() * CELL_SIZE, matrix.height() * CELL_SIZE);
+    let mut result = String::new();
+
+    writeln!(
+        result,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="{f}" text-anchor="middle" dominant-baseline="central">"#,
+        w = width,
+        h = height,
+        f = CELL_SIZE * 3 / 4,
+    )
+    .unwrap();
+
+    for (i, line) in matrix.data.iter().enumerate() {
+        for (j, c) in line.iter().enumerate() {
+            let (x, y) = (j * CELL_SIZE, i * CELL_SIZE);
+            let stroke = match highlighted.contains(&(i, j)) {
+                true => r##" stroke="#1e66f5" stroke-width="2""##,
+                false => "",
+            };
+
+            writeln!(
+                result,
+                r#"<rect x="{}" y="{}" width="{s}" height="{s}" fill="{}"{}/>"#,
+                x,
+                y,
+                kinds[i][j].colour(),
+                stroke,
+                s = CELL_SIZE,
+            )
+            .unwrap();
+            writeln!(
+                result,
+                r#"<text x="{}" y="{}">{}</text>"#,
+                x + CELL_SIZE / 2,
+                y + CELL_SIZE / 2,
+                escape(*c),
+            )
+            .unwrap();
+        }
+    }
+
+    result.push_str("</svg>\n");
+    result
+}
+
+fn escape(c: char) -> String {
+    match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+
+    const TEST_DATA: &str = include_str!("../test_data.txt");
+
+    // drops the escape sequences, leaving the printed characters
+    fn strip_ansi(s: &str) -> String {
+        let mut result = String::new();
+        let mut in_escape = false;
+
+        for c in s.chars() {
+            match (in_escape, c) {
+                (false, '\x1b') => in_escape = true,
+                (false, c) => result.push(c),
+                (true, 'm') => in_escape = false,
+                (true, _) => {}
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_classify() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+        let kinds = classify(&matrix);
+
+        assert_eq!(kinds[0][0], CellKind::PartNumber);
+        assert_eq!(kinds[0][5], CellKind::OtherNumber);
+        assert_eq!(kinds[0][3], CellKind::Empty);
+        assert_eq!(kinds[1][3], CellKind::Gear);
+        assert_eq!(kinds[3][6], CellKind::Symbol);
+        assert_eq!(kinds[4][3], CellKind::BadGear);
+        assert_eq!(kinds[5][7], CellKind::OtherNumber);
+    }
+
+    #[test]
+    fn test_classify_ragged() {
+        let matrix =
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap();
+        let kinds = classify(&matrix);
+
+        assert_eq!(
+            kinds.iter().map(|line| line.len()).collect::<Vec<_>>(),
+            vec![8, 10, 11, 7, 4]
+        );
+        assert_eq!(kinds[2][10], CellKind::Symbol);
+    }
+
+    #[test]
+    fn test_ansi() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+        let output = ansi(&matrix, None);
+
+        assert!(output.starts_with("\x1b[1;32m4\x1b[0m"));
+
+        assert!(strip_ansi(&output).starts_with(TEST_DATA));
+        assert!(!output.contains(";44m"));
+    }
+
+    #[test]
+    fn test_ansi_highlight() {
+        let matrix = Matrix::parse(TEST_DATA, Rules::default()).unwrap();
+
+        // 467 borders 5 cells, among them the gear at (1, 3)
+        let output = ansi(&matrix, Some(0));
+        assert_eq!(output.matches(";44m").count(), 5);
+        assert!(output.contains("\x1b[1;36;44m*\x1b[0m"));
+    }
+
+    #[test]
+    fn test_svg() {
+        let matrix =
+            Matrix::parse(include_str!("../test_data_wide.txt"), Rules::default()).unwrap();
+        let output = svg(&matrix, Some(1));
+
+        assert!(output
+            .starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="192" height="48""#));
+        assert!(output.ends_with("</svg>\n"));
+        assert_eq!(output.matches("<rect").count(), 36);
+        assert_eq!(output.matches("<text").count(), 36);
+
+        // the lone 4 borders 8 cells
+        assert_eq!(output.matches("stroke=").count(), 8);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape('&'), "&amp;");
+        assert_eq!(escape('<'), "&lt;");
+        assert_eq!(escape('#'), "#");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::Matrix;
+use serde::Serialize;
+use std::fmt::Write;
+
+// a number with no symbol on its perimeter
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct OrphanNumber {
+    pub value: u32,
+    pub row: usize,
+    pub column: usize,
+}
+
+// a symbol with no number next to it
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnusedSymbol {
+    pub symbol: char,
+    pub row: usize,
+    pub column: usize,
+}
+
+// a gear whose neighbouring numbers don't satisfy the rules
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FailingGear {
+    pub symbol: char,
+    pub row: usize,
+    pub column: usize,
+    pub numbers: Vec<u32>,
+}
+
+// everything in the schematic that doesn't count towards either sum, in reading order
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub orphan_numbers: Vec<OrphanNumber>,
+    pub unused_symbols: Vec<UnusedSymbol>,
+    pub failing_gears: Vec<FailingGear>,
+}
+
+impl Report {
+    pub fn build_from(matrix: &Matrix) -> Report {
+        let mut orphan_numbers: Vec<OrphanNumber> = matrix
+            .numbers
+            .iter()
+            .filter(|num| !matrix.is_part_number(num))
+            .map(|num| OrphanNumber {
+                value: num.number(),
+                row: num.row,
+                column: num.start,
+            })
+            .collect();
+        orphan_numbers.sort_by_key(|x| (x.row, x.column));
+
+        let mut unused_symbols: Vec<UnusedSymbol> = matrix
+            .symbol_coordinates()
+            .filter(|&(i, j)| matrix.adjacent_numbers(i, j).is_empty())
+            .map(|(i, j)| UnusedSymbol {
+                symbol: matrix.data[i][j],
+                row: i,
+                column: j,
+            })
+            .collect();
+        unused_symbols.sort_by_key(|x| (x.row, x.column));
+
+        let mut failing_gears: Vec<FailingGear> = Vec::new();
+        for (i, j) in matrix.gear_coordinates() {
+            let numbers: Vec<u32> = matrix
+                .adjacent_numbers(i, j)
+                .into_iter()
+                .map(|id| matrix.numbers[id].number())
+                .collect();
+
+            if matrix.rules.gear_ratio(&numbers).is_none() {
+                failing_gears.push(FailingGear {
+                    symbol: matrix.data[i][j],
+                    row: i,
+                    column: j,
+                    numbers,
+                });
+            }
+        }
+        failing_gears.sort_by_key(|x| (x.row, x.column));
+
+        Report {
+            orphan_numbers,
+            unused_symbols,
+            failing_gears,
+        }
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut result = String::new();
+
+        writeln!(result, "orphan numbers ({})", self.orphan_numbers.len()).unwrap();
+        writeln!(result, "  row  col  value").unwrap();
+        for x in &self.orphan_numbers {
+            writeln!(result, "{:>5}  {:>3}  {}", x.row, x.column, x.value).unwrap();
+        }
+
+        writeln!(result, "unused symbols ({})", self.unused_symbols.len()).unwrap();
+        writeln!(result, "  row  col  symbol").unwrap();
+        for x in &self.unused_symbols {
+            writeln!(result, "{:>5}  {:>3}  {}", x.row, x.column, x.symbol).unwrap();
+        }
+
+        writeln!(result, "failing gears ({})", self.failing_gears.len()).unwrap();
+        writeln!(result, "  row  col  symbol  numbers").unwrap();
+        for x in &self.failing_gears {
+            let numbers: Vec<String> = x.numbers.iter().map(|n| n.to_string()).collect();
+            writeln!(
+                result,
+                "{:>5}  {:>3}  {:<6}  {}",
+                x.row,
+                x.column,
+                x.symbol,
+                numbers.join(",")
+            )
+            .unwrap();
+        }
+
+        result
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{NeighbourCount, Rules};
+
+    const TEST_DATA: &str = include_str!("../test_data.txt");
+
+    #[test]
+    fn test_build_from() {
+        let report = Report::build_from(&Matrix::parse(TEST_DATA, Rules::default()).unwrap());
+
+        assert_eq!(
+            report.orphan_numbers,
+            vec![
+                OrphanNumber {
+                    value: 114,
+                    row: 0,
+                    column: 5
+                },
+                OrphanNumber {
+                    value: 58,
+                    row: 5,
+                    column: 7
+                },
+            ]
+        );
+        assert!(report.unused_symbols.is_empty());
+        assert_eq!(
+            report.failing_gears,
+            vec![FailingGear {
+                symbol: '*',
+                row: 4,
+                column: 3,
+                numbers: vec![617]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_build_from_ragged() {
+        let matrix =
+            Matrix::parse(include_str!("../test_data_ragged.txt"), Rules::default()).unwrap();
+        let report = Report::build_from(&matrix);
+
+        // the '#' past the end of the shorter line below it
+        assert_eq!(
+            report.unused_symbols,
+            vec![UnusedSymbol {
+                symbol: '#',
+                row: 2,
+                column: 10
+            }]
+        );
+    }
+
+    #[test]
+    fn test_build_from_with_rules() {
+        let rules = Rules {
+            neighbour_count: NeighbourCount::AtLeast(1),
+            ..Rules::default()
+        };
+        let report = Report::build_from(&Matrix::parse(TEST_DATA, rules).unwrap());
+
+        assert!(report.failing_gears.is_empty());
+    }
+
+    #[test]
+    fn test_to_table() {
+        let report = Report::build_from(&Matrix::parse(TEST_DATA, Rules::default()).unwrap());
+        let table = report.to_table();
+
+        assert!(table.starts_with("orphan numbers (2)\n  row  col  value\n    0    5  114\n"));
+        assert!(table.contains("unused symbols (0)\n"));
+        assert!(table.ends_with(
+            "failing gears (1)\n  row  col  symbol  numbers\n    4    3  *       617\n"
+        ));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let report = Report::build_from(&Matrix::parse(TEST_DATA, Rules::default()).unwrap());
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+
+        assert_eq!(json["orphan_numbers"][1]["value"], 58);
+        assert_eq!(json["unused_symbols"], serde_json::json!([]));
+        assert_eq!(
+            json["failing_gears"][0]["numbers"],
+            serde_json::json!([617])
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::neighbourhood::Neighbourhood;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+// which characters count as symbols
+#[derive(Clone, Debug, PartialEq)]
+pub enum SymbolClass {
+    // any ascii punctuation apart from '.', the puzzle's definition
+    Punctuation,
+    Chars(Vec<char>),
+}
+
+impl SymbolClass {
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            SymbolClass::Punctuation => c.is_ascii_punctuation() && c != '.',
+            SymbolClass::Chars(chars) => chars.contains(&c),
+        }
+    }
+}
+
+// how many numbers must touch a gear for it to count
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NeighbourCount {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl NeighbourCount {
+    pub fn matches(&self, n: usize) -> bool {
+        match *self {
+            NeighbourCount::Exactly(x) => n == x,
+            NeighbourCount::AtLeast(x) => n >= x,
+        }
+    }
+}
+
+// how the numbers touching a gear are folded into its ratio
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Combine {
+    Product,
+    Sum,
+    Max,
+}
+
+impl Combine {
+    // None when the result doesn't fit in a u64, which three large part numbers multiplied
+    // together are already enough for
+    pub fn apply(&self, nums: &[u32]) -> Option<u64> {
+        let mut nums = nums.iter().map(|&x| x as u64);
+
+        match self {
+            Combine::Product => nums.try_fold(1u64, |acc, x| acc.checked_mul(x)),
+            Combine::Sum => nums.try_fold(0u64, |acc, x| acc.checked_add(x)),
+            Combine::Max => Some(nums.max().unwrap_or(0)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub symbols: SymbolClass,
+    pub gears: Vec<char>,
+    pub neighbour_count: NeighbourCount,
+    pub combine: Combine,
+    pub neighbourhood: Neighbourhood,
+}
+
+impl Default for Rules {
+    // part 1 and part 2 as given by the puzzle
+    fn default() -> Self {
+        Rules {
+            symbols: SymbolClass::Punctuation,
+            gears: vec!['*'],
+            neighbour_count: NeighbourCount::Exactly(2),
+            combine: Combine::Product,
+            neighbourhood: Neighbourhood::Moore,
+        }
+    }
+}
+
+impl Rules {
+    pub fn from_file(filepath: &str) -> Result<Rules, RulesError> {
+        let input_string = fs::read_to_string(filepath).map_err(|e| RulesError {
+            line: 0,
+            message: format!("unable to read {}: {}", filepath, e),
+        })?;
+
+        input_string.parse()
+    }
+
+    pub fn is_symbol(&self, c: char) -> bool {
+        self.symbols.contains(c)
+    }
+
+    pub fn is_gear(&self, c: char) -> bool {
+        self.gears.contains(&c)
+    }
+
+    // Some(ratio) if the numbers touching a gear satisfy the neighbour count, matrices check
+    // with ratio_fits that every ratio does fit before asking for any
+    pub fn gear_ratio(&self, nums: &[u32]) -> Option<u64> {
+        if self.neighbour_count.matches(nums.len()) {
+            Some(
+                self.combine
+                    .apply(nums)
+                    .expect("gear ratio too large for a u64"),
+            )
+        } else {
+            None
+        }
+    }
+
+    // false only for numbers that make a gear whose ratio doesn't fit in a u64
+    pub fn ratio_fits(&self, nums: &[u32]) -> bool {
+        !self.neighbour_count.matches(nums.len()) || self.combine.apply(nums).is_some()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RulesError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rules line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+// Example input, any key left out keeps its default:
+//
+// symbols = punctuation
+// gears = *
+// neighbours = exactly 2
+// combine = product
+// neighbourhood = moore
+//
+// `symbols` and `gears` also take a literal list of characters such as `#$*+`
+impl FromStr for Rules {
+    type Err = RulesError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules::default();
+
+        for (n, line) in s.lines().enumerate() {
+            let line_number = n + 1;
+            let err = |message: String| RulesError {
+                line: line_number,
+                message,
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| err(format!("expected `key = value`, found `{}`", line)))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "symbols" => {
+                    rules.symbols = match value {
+                        "punctuation" => SymbolClass::Punctuation,
+                        _ => SymbolClass::Chars(parse_chars(value).map_err(err)?),
+                    }
+                }
+                "gears" => rules.gears = parse_chars(value).map_err(err)?,
+                "neighbours" => {
+                    let (mode, n) = value.rsplit_once(' ').ok_or_else(|| {
+                        err(format!(
+                            "expected `exactly N` or `at least N`, found `{}`",
+                            value
+                        ))
+                    })?;
+                    let n = n
+                        .parse::<usize>()
+                        .map_err(|_| err(format!("invalid neighbour count `{}`", n)))?;
+
+                    rules.neighbour_count = match mode.trim() {
+                        "exactly" => NeighbourCount::Exactly(n),
+                        "at least" => NeighbourCount::AtLeast(n),
+                        other => return Err(err(format!("unknown neighbour mode `{}`", other))),
+                    }
+                }
+                "combine" => {
+                    rules.combine = match value {
+                        "product" => Combine::Product,
+                        "sum" => Combine::Sum,
+                        "max" => Combine::Max,
+                        other => return Err(err(format!("unknown combine operation `{}`", other))),
+                    }
+                }
+                "neighbourhood" => rules.neighbourhood = value.parse().map_err(err)?,
+                other => return Err(err(format!("unknown key `{}`", other))),
+            }
+        }
+
+        Ok(rules)
+    }
+}
+
+fn parse_chars(value: &str) -> Result<Vec<char>, String> {
+    let chars: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if chars.is_empty() {
+        return Err("expected at least one character".to_string());
+    }
+    if let Some(c) = chars.iter().find(|c| c.is_ascii_digit() || **c == '.') {
+        return Err(format!(
+            "`{}` can't be a symbol, digits and '.' are reserved",
+            c
+        ));
+    }
+
+    Ok(chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_rules() {
+        let rules = Rules::default();
+
+        assert!(rules.is_symbol('#'));
+        assert!(rules.is_symbol('*'));
+        assert!(!rules.is_symbol('.'));
+        assert!(!rules.is_symbol('7'));
+
+        assert!(rules.is_gear('*'));
+        assert!(!rules.is_gear('#'));
+
+        assert_eq!(rules.gear_ratio(&[467, 35]), Some(16345));
+        assert_eq!(rules.gear_ratio(&[617]), None);
+        assert_eq!(rules.gear_ratio(&[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_rules_from_str() {
+        let input = "symbols = #$\ngears = *@\n\nneighbours = at least 1\ncombine = max\nneighbourhood = moore:2\n";
+        let rules = input.parse::<Rules>().unwrap();
+
+        let expected = Rules {
+            symbols: SymbolClass::Chars(vec!['#', '$']),
+            gears: vec!['*', '@'],
+            neighbour_count: NeighbourCount::AtLeast(1),
+            combine: Combine::Max,
+            neighbourhood: Neighbourhood::MooreRadius(2),
+        };
+        assert_eq!(rules, expected);
+
+        assert_eq!(rules.gear_ratio(&[617]), Some(617));
+        assert_eq!(rules.gear_ratio(&[]), None);
+    }
+
+    #[test]
+    fn test_combine_overflow() {
+        let nums = [u32::MAX, u32::MAX, u32::MAX];
+
+        assert_eq!(
+            Combine::Product.apply(&nums[..2]),
+            Some(u32::MAX as u64 * u32::MAX as u64)
+        );
+        assert_eq!(Combine::Product.apply(&nums), None);
+        assert_eq!(Combine::Sum.apply(&nums), Some(3 * u32::MAX as u64));
+        assert_eq!(Combine::Max.apply(&[]), Some(0));
+
+        let rules = "neighbours = at least 3".parse::<Rules>().unwrap();
+        assert!(!rules.ratio_fits(&nums));
+        assert!(rules.ratio_fits(&nums[..2]));
+    }
+
+    #[test]
+    fn test_rules_from_str_partial() {
+        let rules = "combine = sum".parse::<Rules>().unwrap();
+
+        assert_eq!(rules.symbols, SymbolClass::Punctuation);
+        assert_eq!(rules.gear_ratio(&[467, 35]), Some(502));
+    }
+
+    #[test]
+    fn test_rules_from_str_errors() {
+        let err = "gears = *\ncombine = mean".parse::<Rules>().unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = "neighbours = roughly 2".parse::<Rules>().unwrap_err();
+        assert_eq!(err.message, "unknown neighbour mode `roughly`");
+
+        assert!("symbols = .#".parse::<Rules>().is_err());
+        assert!("gears =".parse::<Rules>().is_err());
+        assert!("gears".parse::<Rules>().is_err());
+    }
+}
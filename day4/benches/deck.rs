@@ -0,0 +1,106 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use day4::card::{arrange, parse_cards, IdMode};
+use day4::matching::Matcher;
+use day4::scoring::Rule;
+use day4::Card;
+use rayon::prelude::*;
+use std::fmt::Write;
+
+// DAY4_BENCH_CARDS=10000000 for a full size run
+fn deck_size() -> usize {
+    std::env::var("DAY4_BENCH_CARDS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(1_000_000)
+}
+
+// n cards shaped like the puzzle's, 10 winning numbers and 25 numbers below 100 on each
+fn generate_deck(n: usize) -> String {
+    // xorshift, so every run sees the same deck
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut side = |len: usize| {
+        let mut nums: Vec<u64> = Vec::with_capacity(len);
+        while nums.len() < len {
+            let x = next() % 99 + 1;
+            if !nums.contains(&x) {
+                nums.push(x);
+            }
+        }
+        nums.iter()
+            .map(|x| format!("{:>2}", x))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut result = String::with_capacity(n * 128);
+    for id in 1..=n {
+        let winning_nums = side(10);
+        let card_nums = side(25);
+        writeln!(result, "Card {}: {} | {}", id, winning_nums, card_nums).unwrap();
+    }
+    result
+}
+
+fn load(input: &str, matcher: &Matcher) -> Vec<Card> {
+    arrange(parse_cards(input).unwrap(), IdMode::Strict)
+        .unwrap()
+        .into_par_iter()
+        .map(|line| Card::build_from(line, matcher))
+        .collect()
+}
+
+fn bench_matchers(c: &mut Criterion) {
+    let n = deck_size();
+    let cards = load(&generate_deck(n), &Matcher::default());
+
+    let mut group = c.benchmark_group("match");
+    group.throughput(Throughput::Elements(n as u64));
+    group.sample_size(10);
+
+    for matcher in [Matcher::Scan, Matcher::HashSet, Matcher::default()] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(matcher),
+            &matcher,
+            |b, matcher| {
+                b.iter(|| {
+                    cards
+                        .par_iter()
+                        .map(|card| matcher.count(&card.winning_nums, &card.card_nums))
+                        .sum::<usize>()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_deck(c: &mut Criterion) {
+    let n = deck_size();
+    let input = generate_deck(n);
+
+    let mut group = c.benchmark_group("deck");
+    group.throughput(Throughput::Elements(n as u64));
+    group.sample_size(10);
+
+    group.bench_function("parse", |b| {
+        b.iter(|| load(black_box(&input), &Matcher::default()))
+    });
+
+    let cards = load(&input, &Matcher::default());
+    for rule in [Rule::Doubling, Rule::Cascade] {
+        group.bench_with_input(BenchmarkId::new("score", rule), &rule, |b, rule| {
+            b.iter(|| rule.evaluate(black_box(&cards)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_matchers, bench_deck);
+criterion_main!(benches);
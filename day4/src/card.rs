@@ -0,0 +1,332 @@
+use rayon::prelude::*;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+// a card as written on one line of the input
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardLine {
+    pub line: usize,
+    pub id: usize,
+    pub winning_nums: Vec<u32>,
+    pub card_nums: Vec<u32>,
+}
+
+// line and column are both counted from 1
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn header() -> &'static Regex {
+    static HEADER: OnceLock<Regex> = OnceLock::new();
+    HEADER.get_or_init(|| Regex::new(r"^\s*Card\s+[0-9]+\s*:").unwrap())
+}
+
+impl CardLine {
+    // Example input: "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11"
+    // numbers may be separated by any run of spaces or tabs
+    pub fn parse(line: usize, s: &str) -> Result<CardLine, ParseError> {
+        let err = |offset: usize, message: String| ParseError {
+            line,
+            column: offset + 1,
+            message,
+        };
+
+        let header = header()
+            .find(s)
+            .ok_or_else(|| err(0, format!("expected `Card N:`, found `{}`", s)))?;
+
+        // the id is the run of digits closing the header, before any space and the ':'
+        let id = s[..header.end() - 1].trim_end();
+        let id_start = id.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let id = id[id_start..].parse::<usize>().map_err(|_| {
+            err(
+                id_start,
+                format!("card id `{}` is too large", &id[id_start..]),
+            )
+        })?;
+
+        // split into winning nums and own nums
+        let start = header.end();
+        let bar = start
+            + s[start..].find('|').ok_or_else(|| {
+                err(
+                    s.len(),
+                    "expected `|` between the winning numbers and the card's numbers".to_string(),
+                )
+            })?;
+
+        Ok(CardLine {
+            line,
+            id,
+            winning_nums: parse_side(line, s, start, bar)?,
+            card_nums: parse_side(line, s, bar + 1, s.len())?,
+        })
+    }
+}
+
+// the numbers in s[start..end], none of them repeated
+fn parse_side(line: usize, s: &str, start: usize, end: usize) -> Result<Vec<u32>, ParseError> {
+    let mut result: Vec<u32> = Vec::new();
+
+    for token in s[start..end].split_whitespace() {
+        // byte offset of the token within the line
+        let offset = token.as_ptr() as usize - s.as_ptr() as usize;
+        let err = |message: String| ParseError {
+            line,
+            column: offset + 1,
+            message,
+        };
+
+        // u32 parsing alone would let a leading '+' through
+        let x = match token.bytes().all(|b| b.is_ascii_digit()) {
+            true => token.parse::<u32>().ok(),
+            false => None,
+        }
+        .ok_or_else(|| err(format!("invalid number `{}`", token)))?;
+
+        if result.contains(&x) {
+            return Err(err(format!("duplicate number {}", x)));
+        }
+        result.push(x);
+    }
+
+    Ok(result)
+}
+
+// every card in the input, skipping blank lines
+// lines are parsed in parallel, the error reported is still the first in the input
+pub fn parse_cards(input: &str) -> Result<Vec<CardLine>, ParseError> {
+    let lines: Vec<(usize, &str)> = input
+        .lines()
+        .enumerate()
+        .filter(|(_, s)| !s.trim().is_empty())
+        .collect();
+
+    let cards: Vec<Result<CardLine, ParseError>> = lines
+        .into_par_iter()
+        .map(|(i, s)| CardLine::parse(i + 1, s))
+        .collect();
+
+    cards.into_iter().collect()
+}
+
+// how a deck whose ids don't run 1, 2, 3, .. in order is handled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IdMode {
+    // reject any missing, repeated or out of order id
+    Strict,
+    // sort the cards by id, a card then wins every card with an id in the range it wins,
+    // so wins landing on a missing id are lost and a repeated id is won as many times as it
+    // appears
+    Lenient,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DeckError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DeckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for DeckError {}
+
+// the cards in the order they are played
+pub fn arrange(mut cards: Vec<CardLine>, mode: IdMode) -> Result<Vec<CardLine>, DeckError> {
+    match mode {
+        IdMode::Strict => {
+            // id -> line it first appears on
+            let mut seen: HashMap<usize, usize> = HashMap::new();
+
+            for (n, card) in cards.iter().enumerate() {
+                let expected = n + 1;
+                let err = |message: String| DeckError {
+                    line: card.line,
+                    message,
+                };
+
+                if let Some(line) = seen.get(&card.id) {
+                    return Err(err(format!(
+                        "card {} is repeated, first seen on line {}",
+                        card.id, line
+                    )));
+                }
+                if card.id != expected {
+                    return match cards[n..].iter().any(|x| x.id == expected) {
+                        true => Err(err(format!(
+                            "card {} comes before card {}",
+                            card.id, expected
+                        ))),
+                        false => Err(err(format!("card {} is missing", expected))),
+                    };
+                }
+                seen.insert(card.id, card.line);
+            }
+        }
+        IdMode::Lenient => cards.sort_by_key(|card| card.id),
+    }
+
+    Ok(cards)
+}
+
+impl fmt::Display for IdMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdMode::Strict => write!(f, "strict"),
+            IdMode::Lenient => write!(f, "lenient"),
+        }
+    }
+}
+
+// Example input: "strict" or "lenient"
+impl FromStr for IdMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "strict" => Ok(IdMode::Strict),
+            "lenient" => Ok(IdMode::Lenient),
+            other => Err(format!(
+                "unknown id mode `{}`, expected strict or lenient",
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let card = CardLine::parse(1, "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11 ").unwrap();
+
+        assert_eq!(card.id, 6);
+        assert_eq!(card.winning_nums, vec![31, 18, 13, 56, 72]);
+        assert_eq!(card.card_nums, vec![74, 77, 10, 23, 35, 67, 36, 11]);
+    }
+
+    #[test]
+    fn test_parse_whitespace() {
+        let card = CardLine::parse(1, "Card\t  12:\t1   2\t\t3 |  4\t 5").unwrap();
+
+        assert_eq!(card.id, 12);
+        assert_eq!(card.winning_nums, vec![1, 2, 3]);
+        assert_eq!(card.card_nums, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = CardLine::parse(3, "Crd 1: 1 2 | 3").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+
+        let err = CardLine::parse(1, "Card 1: 1 2 3").unwrap_err();
+        assert_eq!(err.column, 14);
+
+        let err = CardLine::parse(1, "Card 1: 1 x2 | 3").unwrap_err();
+        assert_eq!(err.column, 11);
+        assert_eq!(err.message, "invalid number `x2`");
+
+        let err = CardLine::parse(1, "Card 1: 1 2 | 3 | 4").unwrap_err();
+        assert_eq!(err.column, 17);
+
+        assert!(CardLine::parse(1, "Card 1: +1 | 3").is_err());
+        assert!(CardLine::parse(1, "Card 1: 1 | 99999999999").is_err());
+        assert!(CardLine::parse(1, "Card 99999999999999999999: 1 | 3").is_err());
+    }
+
+    #[test]
+    fn test_parse_duplicates() {
+        let err = CardLine::parse(1, "Card 1: 5 7 5 | 5 7").unwrap_err();
+        assert_eq!(err.column, 13);
+        assert_eq!(err.message, "duplicate number 5");
+
+        // the same number on both sides is a match, not a duplicate
+        assert!(CardLine::parse(1, "Card 1: 5 7 | 5 7").is_ok());
+    }
+
+    #[test]
+    fn test_parse_cards() {
+        let cards = parse_cards("Card 1: 1 | 1\n\nCard 2: 2 | 3\n").unwrap();
+        assert_eq!(
+            cards.iter().map(|card| card.id).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        let err = parse_cards("Card 1: 1 | 1\n\nCard 2 2 | 3\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 1: expected `Card N:`, found `Card 2 2 | 3`"
+        );
+    }
+
+    fn ids(cards: &[CardLine]) -> Vec<usize> {
+        cards.iter().map(|card| card.id).collect()
+    }
+
+    #[test]
+    fn test_arrange_strict() {
+        let cards = parse_cards("Card 1: 1 | 1\nCard 2: 2 | 2\nCard 3: 3 | 3").unwrap();
+        assert_eq!(ids(&arrange(cards, IdMode::Strict).unwrap()), vec![1, 2, 3]);
+
+        let cards = parse_cards("Card 1: 1 | 1\n\nCard 3: 3 | 3").unwrap();
+        assert_eq!(
+            arrange(cards, IdMode::Strict).unwrap_err().to_string(),
+            "line 3: card 2 is missing"
+        );
+
+        let cards = parse_cards("Card 1: 1 | 1\nCard 3: 3 | 3\nCard 2: 2 | 2").unwrap();
+        assert_eq!(
+            arrange(cards, IdMode::Strict).unwrap_err().to_string(),
+            "line 2: card 3 comes before card 2"
+        );
+
+        let cards = parse_cards("Card 1: 1 | 1\nCard 2: 2 | 2\nCard 2: 2 | 2").unwrap();
+        assert_eq!(
+            arrange(cards, IdMode::Strict).unwrap_err().to_string(),
+            "line 3: card 2 is repeated, first seen on line 2"
+        );
+    }
+
+    #[test]
+    fn test_arrange_lenient() {
+        let cards =
+            parse_cards("Card 5: 1 | 1\nCard 2: 2 | 2\nCard 2: 3 | 3\nCard 1: 4 | 4").unwrap();
+        let cards = arrange(cards, IdMode::Lenient).unwrap();
+
+        assert_eq!(ids(&cards), vec![1, 2, 2, 5]);
+        // repeated ids keep the order they were written in
+        assert_eq!((cards[1].line, cards[2].line), (2, 3));
+    }
+
+    #[test]
+    fn test_id_mode_from_str() {
+        for mode in [IdMode::Strict, IdMode::Lenient] {
+            assert_eq!(mode.to_string().parse::<IdMode>(), Ok(mode));
+        }
+        assert!("loose".parse::<IdMode>().is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use crate::Card;
+
+// cards numbered from 1 that only carry a score
+pub fn cards_with_scores(scores: &[usize]) -> Vec<Card> {
+    scores
+        .iter()
+        .enumerate()
+        .map(|(i, &score)| {
+            let mut card = Card::new(i + 1);
+            card.score = score;
+            card
+        })
+        .collect()
+}
+
+// the example's match counts
+pub fn example() -> Vec<Card> {
+    cards_with_scores(&[4, 2, 2, 1, 0, 0])
+}
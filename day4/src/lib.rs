@@ -0,0 +1,183 @@
+pub mod card;
+#[cfg(test)]
+mod fixtures;
+pub mod matching;
+pub mod provenance;
+pub mod scoring;
+
+use card::CardLine;
+use matching::Matcher;
+
+#[derive(Clone, Debug)]
+pub struct Card {
+    pub id: usize,
+    pub winning_nums: Vec<u32>,
+    pub card_nums: Vec<u32>,
+    // the number of matches
+    pub score: usize,
+}
+
+impl Card {
+    pub fn new(id: usize) -> Self {
+        Self {
+            id,
+            winning_nums: Vec::new(),
+            card_nums: Vec::new(),
+            score: 0,
+        }
+    }
+
+    pub fn build_from(line: CardLine, matcher: &Matcher) -> Card {
+        let mut card = Card::new(line.id);
+
+        card.winning_nums = line.winning_nums;
+        card.card_nums = line.card_nums;
+        card.score = card.calculate_score(matcher);
+        card
+    }
+
+    fn calculate_score(&self, matcher: &Matcher) -> usize {
+        matcher.count(&self.winning_nums, &self.card_nums)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use card::{arrange, parse_cards, IdMode};
+    use fixtures::cards_with_scores;
+    use num_bigint::BigUint;
+    use proptest::prelude::*;
+    use scoring::Rule;
+    use std::collections::HashMap;
+
+    // the original tally, one entry per card held, kept to check the cascade rule against
+    fn process(cards: Vec<Card>) -> Vec<usize> {
+        let last_id = cards.len();
+
+        // store running tally card ids
+        let mut tally: Vec<usize> = Vec::new();
+
+        // hashmap for lookup
+        let cards_map: HashMap<usize, Card> =
+            cards.into_iter().map(|card| (card.id, card)).collect();
+
+        // every card won by each card, directly or through the cards it wins
+        let mut also_wins_map: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        // iterate through all the cards but in reverse
+        for i in (1..=last_id).rev() {
+            let mut also_wins: Vec<usize> = Vec::new();
+
+            let card = cards_map.get(&i).unwrap();
+
+            let lookup_cards = (card.id + 1)..=(card.score + card.id);
+
+            // look up cards that are immediately won
+            for k in lookup_cards {
+                if cards_map.contains_key(&k) {
+                    // push the card won
+                    also_wins.push(k);
+                    // also push all the cards that this won card has also won
+                    also_wins.extend(
+                        also_wins_map.get(&k).unwrap(), // should be unreachable
+                    );
+                }
+            }
+            // update this cards also_win state
+            also_wins_map.insert(i, also_wins.clone());
+
+            // update tally of num cards
+            tally.push(i);
+            tally.extend(also_wins);
+        }
+
+        tally
+    }
+
+    const TEST_DATA: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11";
+
+    fn test_cards() -> Vec<Card> {
+        parse_cards(TEST_DATA)
+            .unwrap()
+            .into_iter()
+            .map(|line| Card::build_from(line, &Matcher::default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_build_from() {
+        let cards = test_cards();
+
+        assert_eq!(cards[0].winning_nums, vec![41, 48, 83, 86, 17]);
+        assert_eq!(cards[0].card_nums, vec![83, 86, 6, 31, 17, 9, 48, 53]);
+        assert_eq!(
+            cards.iter().map(|card| card.score).collect::<Vec<_>>(),
+            vec![4, 2, 2, 1, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_cascade() {
+        assert_eq!(Rule::Cascade.evaluate(&test_cards()), BigUint::from(30_u32));
+        assert_eq!(process(test_cards()).len(), 30);
+
+        assert_eq!(Rule::Cascade.evaluate(&[]), BigUint::ZERO);
+    }
+
+    #[test]
+    fn test_cascade_wins_past_the_end() {
+        // the last card's wins have nothing to land on
+        assert_eq!(
+            Rule::Cascade.evaluate(&cards_with_scores(&[1, 5])),
+            BigUint::from(3_u32)
+        );
+    }
+
+    #[test]
+    fn test_cascade_beyond_u64() {
+        // every card winning every card after it doubles the count each time
+        let n = 100;
+        let cards = cards_with_scores(&vec![n; n]);
+
+        let expected = (BigUint::from(1_u32) << n) - 1_u32;
+        assert_eq!(Rule::Cascade.evaluate(&cards), expected);
+        assert!(expected > BigUint::from(u64::MAX));
+    }
+
+    fn lenient_cards(input: &str) -> Vec<Card> {
+        arrange(parse_cards(input).unwrap(), IdMode::Lenient)
+            .unwrap()
+            .into_iter()
+            .map(|line| Card::build_from(line, &Matcher::default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_cascade_lenient_ids() {
+        // 1 wins 2 and 3 but there is no 2, and 3's win lands on the missing 4
+        let cards = lenient_cards("Card 3: 1 | 1\nCard 1: 1 2 | 1 2\nCard 5: 9 | 9");
+        assert_eq!(Rule::Cascade.evaluate(&cards), BigUint::from(1_u32 + 2 + 1));
+
+        // both cards numbered 2 are won
+        let cards = lenient_cards("Card 1: 1 | 1\nCard 2: 5 | 6\nCard 2: 7 | 8");
+        assert_eq!(Rule::Cascade.evaluate(&cards), BigUint::from(1_u32 + 2 + 2));
+    }
+
+    proptest! {
+        #[test]
+        fn cascade_matches_process(scores in prop::collection::vec(0..6usize, 0..14)) {
+            let cards = cards_with_scores(&scores);
+
+            prop_assert_eq!(
+                Rule::Cascade.evaluate(&cards),
+                BigUint::from(process(cards).len())
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+// how the numbers a card shares with its winning numbers are counted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Matcher {
+    // a bitset over 0..limit, a card holding a number at or above the limit falls back to a
+    // hash set
+    Bitset(u32),
+    HashSet,
+    // every number looked up in the winning numbers one by one
+    Scan,
+}
+
+// the largest bitset limit accepted, its words are allocated afresh for every card so anything
+// bigger costs more than the hash set it stands in for
+pub const MAX_BITSET_LIMIT: u32 = 1 << 16;
+
+impl Default for Matcher {
+    // puzzle numbers are all below 100
+    fn default() -> Self {
+        Matcher::Bitset(100)
+    }
+}
+
+impl Matcher {
+    pub fn count(&self, winning_nums: &[u32], card_nums: &[u32]) -> usize {
+        match *self {
+            Matcher::Bitset(limit) => {
+                match winning_nums.iter().chain(card_nums).all(|&x| x < limit) {
+                    true => count_bitset(limit, winning_nums, card_nums),
+                    false => count_hash_set(winning_nums, card_nums),
+                }
+            }
+            Matcher::HashSet => count_hash_set(winning_nums, card_nums),
+            Matcher::Scan => card_nums
+                .iter()
+                .filter(|x| winning_nums.contains(x))
+                .count(),
+        }
+    }
+}
+
+// every number is below limit
+fn count_bitset(limit: u32, winning_nums: &[u32], card_nums: &[u32]) -> usize {
+    // a single word covers the puzzle's numbers without allocating
+    if limit <= 128 {
+        let bits = winning_nums.iter().fold(0_u128, |bits, &x| bits | 1 << x);
+        return card_nums.iter().filter(|&&x| bits & 1 << x != 0).count();
+    }
+
+    let mut bits: Vec<u64> = vec![0; (limit as usize).div_ceil(64)];
+    for &x in winning_nums {
+        bits[x as usize / 64] |= 1 << (x % 64);
+    }
+
+    card_nums
+        .iter()
+        .filter(|&&x| bits[x as usize / 64] & 1 << (x % 64) != 0)
+        .count()
+}
+
+fn count_hash_set(winning_nums: &[u32], card_nums: &[u32]) -> usize {
+    let winning_nums: HashSet<u32> = winning_nums.iter().copied().collect();
+
+    card_nums
+        .iter()
+        .filter(|x| winning_nums.contains(x))
+        .count()
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Matcher::Bitset(limit) => write!(f, "bitset:{}", limit),
+            Matcher::HashSet => write!(f, "hashset"),
+            Matcher::Scan => write!(f, "scan"),
+        }
+    }
+}
+
+// Example input: "bitset:100", "hashset" or "scan", a bare "bitset" covers numbers below 100
+// and the limit is at most MAX_BITSET_LIMIT
+impl FromStr for Matcher {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "bitset" => Ok(Matcher::default()),
+            "hashset" => Ok(Matcher::HashSet),
+            "scan" => Ok(Matcher::Scan),
+            other => {
+                let limit = other
+                    .strip_prefix("bitset:")
+                    .and_then(|limit| limit.parse::<u32>().ok())
+                    .filter(|&limit| limit <= MAX_BITSET_LIMIT)
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown matcher `{}`, expected bitset:N with N at most {}, hashset or scan",
+                            other, MAX_BITSET_LIMIT
+                        )
+                    })?;
+                Ok(Matcher::Bitset(limit))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const MATCHERS: [Matcher; 5] = [
+        Matcher::Bitset(100),
+        Matcher::Bitset(1000),
+        Matcher::Bitset(10),
+        Matcher::HashSet,
+        Matcher::Scan,
+    ];
+
+    #[test]
+    fn test_count() {
+        let winning_nums = [41, 48, 83, 86, 17];
+        let card_nums = [83, 86, 6, 31, 17, 9, 48, 53];
+
+        for matcher in MATCHERS {
+            assert_eq!(matcher.count(&winning_nums, &card_nums), 4);
+            assert_eq!(matcher.count(&[], &card_nums), 0);
+        }
+    }
+
+    #[test]
+    fn test_count_above_limit() {
+        // 127 is the last number the single word bitset covers, 500 falls back to a hash set
+        assert_eq!(Matcher::Bitset(128).count(&[0, 127], &[127, 5]), 1);
+        assert_eq!(Matcher::Bitset(100).count(&[500, 3], &[500, 3, 4]), 2);
+        assert_eq!(Matcher::Bitset(1000).count(&[999, 64], &[999, 64, 63]), 2);
+    }
+
+    #[test]
+    fn test_matcher_from_str() {
+        for matcher in MATCHERS {
+            assert_eq!(matcher.to_string().parse::<Matcher>(), Ok(matcher));
+        }
+
+        assert_eq!("bitset".parse::<Matcher>(), Ok(Matcher::Bitset(100)));
+        assert!("bitset:x".parse::<Matcher>().is_err());
+        assert_eq!(
+            "bitset:65536".parse::<Matcher>(),
+            Ok(Matcher::Bitset(MAX_BITSET_LIMIT))
+        );
+        assert!("bitset:65537".parse::<Matcher>().is_err());
+        assert!("bitset:4294967295".parse::<Matcher>().is_err());
+        assert!("btree".parse::<Matcher>().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn matchers_agree(
+            winning_nums in prop::collection::hash_set(0..300u32, 0..12),
+            card_nums in prop::collection::hash_set(0..300u32, 0..30),
+        ) {
+            let winning_nums: Vec<u32> = winning_nums.into_iter().collect();
+            let card_nums: Vec<u32> = card_nums.into_iter().collect();
+
+            let expected = Matcher::Scan.count(&winning_nums, &card_nums);
+            for matcher in MATCHERS {
+                prop_assert_eq!(matcher.count(&winning_nums, &card_nums), expected);
+            }
+        }
+    }
+}
@@ -1,16 +1,26 @@
+mod card;
+mod matching;
+
+use card::{parse_cards, CardLine};
+use matching::Matcher;
 use std::fs;
 
 fn main() {
     let input = read_file("data.txt").unwrap();
 
-    let cards = input
-        .lines()
-        .map(|s| Card::from_string(s.to_string()))
+    let cards = parse_cards(&input)
+        .unwrap_or_else(|e| {
+            eprintln!("data.txt: {}", e);
+            std::process::exit(1);
+        })
+        .into_iter()
+        .map(Card::from_line)
         .collect::<Vec<Card>>();
 
+    let matcher = Matcher::default();
     let result = cards
         .iter()
-        .map(|card| card.calculate_points())
+        .map(|card| card.calculate_points(&matcher))
         .sum::<i32>();
 
     println!("{}",result);
@@ -23,43 +33,15 @@ struct Card {
 }
 
 impl Card {
-    fn from_string(s: String) -> Card {
-        let parts: Vec<&str> = s.split(':').collect();
-        if parts.len() != 2 {
-            unimplemented!()
-        }
-    
-        // split into winning nums and own nums
-        let nums: Vec<&str> = parts[1].split("|").collect();
-        if nums.len() != 2 {
-            unimplemented!()
+    fn from_line(line: CardLine) -> Card {
+        Card {
+            winning_nums: line.winning_nums,
+            card_nums: line.card_nums,
         }
-    
-        let winning_nums = nums[0]
-            .trim()
-            .replace("  ", " ")
-            .split(" ")
-            .map(|c| c.parse::<u32>().unwrap())
-            .collect::<Vec<u32>>();
-    
-        let card_nums = nums[1]
-            .trim()
-            .replace("  ", " ")
-            .split(" ")
-            .map(|c| c.parse::<u32>().unwrap())
-            .collect::<Vec<u32>>();
-    
-        Card{winning_nums, card_nums}
     }
 
-    fn calculate_points(&self) -> i32 {
-        let mut hits = 0_usize;
-
-        for x in &self.card_nums {
-            if self.winning_nums.contains(x) {
-                hits += 1;
-            }
-        }
+    fn calculate_points(&self, matcher: &Matcher) -> i32 {
+        let hits = matcher.count(&self.winning_nums, &self.card_nums);
 
         match hits {
             0 => { 0 },
@@ -77,9 +59,9 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_from_string() {
-        let s = "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11 ".to_string();
-        let card = Card::from_string(s);
+    fn test_from_line() {
+        let s = "Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11 ";
+        let card = Card::from_line(CardLine::parse(1, s).unwrap());
 
         assert_eq!(card.winning_nums, vec![31, 18, 13, 56, 72]);
         assert_eq!(card.card_nums, vec![74, 77, 10, 23, 35, 67, 36, 11]);
@@ -92,14 +74,14 @@ mod test {
             card_nums: vec![74, 77, 10, 23, 35, 67, 36, 11],
         };
 
-        assert_eq!(card.calculate_points(), 0);
+        assert_eq!(card.calculate_points(&Matcher::default()), 0);
 
         let card = Card {
             winning_nums: vec![31, 18, 13, 56, 72],
             card_nums: vec![31, 18, 13, 56, 72],
         };
 
-        assert_eq!(card.calculate_points(), 16);
+        assert_eq!(card.calculate_points(&Matcher::default()), 16);
     }
 
 }
\ No newline at end of file
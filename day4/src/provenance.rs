@@ -0,0 +1,376 @@
+use crate::scoring::won_range;
+use crate::Card;
+use num_bigint::BigUint;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+
+// where the copies of one card come from and what they go on to win
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance {
+    pub card: usize,
+    pub copies: BigUint,
+    // cards winning a copy of this card directly, with the copies each of them gives it
+    pub parents: Vec<(usize, BigUint)>,
+    // every card leading to this one through a chain of wins, in id order
+    pub ancestors: Vec<usize>,
+    // every card won by this card, directly or through the cards it wins, in id order
+    pub descendants: Vec<usize>,
+    // the ids each card above wins, and the copies held of it
+    wins: BTreeMap<usize, Vec<usize>>,
+    copies_of: BTreeMap<usize, BigUint>,
+}
+
+// the deepest level of the spawn tree that is indented
+const MAX_INDENT: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum ProvenanceError {
+    NoCard(usize),
+    RepeatedId(usize),
+}
+
+impl fmt::Display for ProvenanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProvenanceError::NoCard(id) => write!(f, "no card {}", id),
+            ProvenanceError::RepeatedId(id) => {
+                write!(
+                    f,
+                    "card {} is repeated, explain needs every id to be distinct",
+                    id
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProvenanceError {}
+
+impl Provenance {
+    // the card with this id, cap is the most cards one card can win
+    // the tree is keyed by id, so a lenient deck repeating any id is refused rather than having
+    // one copy of the card stand in for the other
+    pub fn build(cards: &[Card], id: usize, cap: usize) -> Result<Provenance, ProvenanceError> {
+        // cards are in id order, so repeats sit next to each other
+        if let Some(w) = cards.windows(2).find(|w| w[0].id == w[1].id) {
+            return Err(ProvenanceError::RepeatedId(w[0].id));
+        }
+        let k = cards
+            .iter()
+            .position(|card| card.id == id)
+            .ok_or(ProvenanceError::NoCard(id))?;
+
+        // positions won by each card
+        let won: Vec<Range<usize>> = (0..cards.len())
+            .map(|i| won_range(cards, i, cards[i].score.min(cap)))
+            .collect();
+
+        let mut copies: Vec<BigUint> = vec![BigUint::from(1_u32); cards.len()];
+        for i in 0..cards.len() {
+            for j in won[i].clone() {
+                copies[j] = &copies[j] + &copies[i];
+            }
+        }
+
+        // ids are distinct, so each card's wins start right after it and the cards winning a
+        // copy of k are the ones before it whose run reaches past it
+        let parents: Vec<usize> = (0..k).filter(|&i| won[i].end > k).collect();
+
+        // walking back from k, a card is an ancestor when its run reaches the nearest ancestor
+        // found so far, every other one sitting further on, so one pass finds them all
+        let mut ancestors: Vec<usize> = Vec::new();
+        let mut nearest = k;
+        for i in (0..k).rev() {
+            if won[i].end > nearest {
+                ancestors.push(i);
+                nearest = i;
+            }
+        }
+        ancestors.reverse();
+
+        // and forward through the wins for the descendants
+
+        let mut descendants: BTreeSet<usize> = BTreeSet::new();
+        let mut stack: Vec<usize> = won[k].clone().collect();
+        while let Some(j) = stack.pop() {
+            if descendants.insert(j) {
+                stack.extend(won[j].clone());
+            }
+        }
+
+        let involved = ancestors
+            .iter()
+            .chain(descendants.iter())
+            .chain(std::iter::once(&k));
+        let mut wins: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        let mut copies_of: BTreeMap<usize, BigUint> = BTreeMap::new();
+        for &i in involved {
+            wins.insert(cards[i].id, won[i].clone().map(|j| cards[j].id).collect());
+            copies_of.insert(cards[i].id, copies[i].clone());
+        }
+
+        Ok(Provenance {
+            card: id,
+            copies: copies[k].clone(),
+            parents: parents
+                .into_iter()
+                .map(|i| (cards[i].id, copies[i].clone()))
+                .collect(),
+            ancestors: ancestors.into_iter().map(|i| cards[i].id).collect(),
+            descendants: descendants.into_iter().map(|i| cards[i].id).collect(),
+            wins,
+            copies_of,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+
+        writeln!(result, "card {}: {} copies", self.card, self.copies).unwrap();
+        writeln!(result, "  1 original").unwrap();
+        for (id, copies) in &self.parents {
+            writeln!(result, "  {} won by card {}", copies, id).unwrap();
+        }
+
+        let ancestors: Vec<String> = self.ancestors.iter().map(|id| id.to_string()).collect();
+        writeln!(result, "ancestors: {}", ancestors.join(", ")).unwrap();
+
+        writeln!(result, "spawn tree:").unwrap();
+        self.write_tree(&mut result);
+
+        result
+    }
+
+    // a card and everything it wins, each card expanded only the first time it comes up
+    // a chain of wins can run as deep as the deck is long, so the tree is walked with a stack
+    // rather than recursion and lines past MAX_INDENT levels are marked with their depth instead
+    // of being indented further
+    fn write_tree(&self, result: &mut String) {
+        let mut expanded: HashSet<usize> = HashSet::new();
+        let mut stack: Vec<(usize, usize)> = vec![(self.card, 0)];
+
+        while let Some((id, depth)) = stack.pop() {
+            let indent = "  ".repeat(depth.min(MAX_INDENT) + 1);
+            let level = match depth > MAX_INDENT {
+                true => format!("[{}] ", depth),
+                false => String::new(),
+            };
+
+            if !expanded.insert(id) && !self.wins[&id].is_empty() {
+                writeln!(result, "{}{}card {} (as above)", indent, level, id).unwrap();
+                continue;
+            }
+            writeln!(
+                result,
+                "{}{}card {} x{}",
+                indent, level, id, self.copies_of[&id]
+            )
+            .unwrap();
+
+            // pushed last first, so the children come off in id order
+            stack.extend(self.wins[&id].iter().rev().map(|&child| (child, depth + 1)));
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // copies can outgrow any json number, so they're written as strings
+        let value = json!({
+            "card": self.card,
+            "copies": self.copies.to_string(),
+            "parents": self.parents
+                .iter()
+                .map(|(id, copies)| json!({ "card": id, "copies": copies.to_string() }))
+                .collect::<Vec<_>>(),
+            "ancestors": self.ancestors,
+            "descendants": self.descendants,
+            "wins": self.wins
+                .iter()
+                .map(|(id, wins)| json!({
+                    "card": id,
+                    "copies": self.copies_of[id].to_string(),
+                    "wins": wins,
+                }))
+                .collect::<Vec<_>>(),
+        });
+
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    // the cascade through this card, ancestors above it and descendants below
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph cascade {\n");
+
+        for (id, copies) in &self.copies_of {
+            let style = match *id == self.card {
+                true => ", style=filled, fillcolor=\"#f9e2af\"",
+                false => "",
+            };
+            writeln!(
+                result,
+                "  c{} [label=\"card {}\\n{} copies\"{}];",
+                id, id, copies, style
+            )
+            .unwrap();
+        }
+
+        for (id, wins) in &self.wins {
+            for child in wins.iter().filter(|x| self.copies_of.contains_key(x)) {
+                writeln!(result, "  c{} -> c{};", id, child).unwrap();
+            }
+        }
+
+        result.push_str("}\n");
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{arrange, parse_cards, IdMode};
+    use crate::fixtures::{cards_with_scores, example};
+    use crate::matching::Matcher;
+
+    #[test]
+    fn test_build() {
+        let provenance = Provenance::build(&example(), 4, usize::MAX).unwrap();
+
+        assert_eq!(provenance.copies, BigUint::from(8_u32));
+        assert_eq!(
+            provenance.parents,
+            vec![
+                (1, BigUint::from(1_u32)),
+                (2, BigUint::from(2_u32)),
+                (3, BigUint::from(4_u32))
+            ]
+        );
+        assert_eq!(provenance.ancestors, vec![1, 2, 3]);
+        assert_eq!(provenance.descendants, vec![5]);
+
+        assert_eq!(
+            Provenance::build(&example(), 7, usize::MAX),
+            Err(ProvenanceError::NoCard(7))
+        );
+    }
+
+    #[test]
+    fn test_build_repeated_id() {
+        // a lenient deck playing two card 2s, only the first of them winning card 3
+        let lines =
+            parse_cards("Card 1: 1 2 | 1 2\nCard 2: 5 | 5\nCard 2: 7 | 8\nCard 3: 1 | 2").unwrap();
+        let cards: Vec<Card> = arrange(lines, IdMode::Lenient)
+            .unwrap()
+            .into_iter()
+            .map(|line| Card::build_from(line, &Matcher::default()))
+            .collect();
+
+        for id in [1, 2, 3] {
+            assert_eq!(
+                Provenance::build(&cards, id, usize::MAX),
+                Err(ProvenanceError::RepeatedId(2))
+            );
+        }
+        assert_eq!(
+            ProvenanceError::RepeatedId(2).to_string(),
+            "card 2 is repeated, explain needs every id to be distinct"
+        );
+    }
+
+    #[test]
+    fn test_build_totals() {
+        // the copies of every card add up to the cascade's score
+        let cards = example();
+        let total: BigUint = (1..=6)
+            .map(|id| Provenance::build(&cards, id, usize::MAX).unwrap().copies)
+            .sum();
+
+        assert_eq!(total, BigUint::from(30_u32));
+    }
+
+    #[test]
+    fn test_build_capped() {
+        let provenance = Provenance::build(&example(), 4, 1).unwrap();
+
+        assert_eq!(provenance.copies, BigUint::from(4_u32));
+        assert_eq!(provenance.parents, vec![(3, BigUint::from(3_u32))]);
+        assert_eq!(provenance.ancestors, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_to_text() {
+        let text = Provenance::build(&example(), 2, usize::MAX)
+            .unwrap()
+            .to_text();
+
+        assert_eq!(
+            text,
+            "card 2: 2 copies
+  1 original
+  1 won by card 1
+ancestors: 1
+spawn tree:
+  card 2 x2
+    card 3 x4
+      card 4 x8
+        card 5 x14
+      card 5 x14
+    card 4 (as above)
+"
+        );
+    }
+
+    #[test]
+    fn test_long_chain() {
+        // every card wins the next, one chain running the length of the deck with card j held
+        // j times
+        let n = 100_000;
+        let cards = cards_with_scores(&vec![1; n]);
+
+        let last = Provenance::build(&cards, n, usize::MAX).unwrap();
+        assert_eq!(last.ancestors, (1..n).collect::<Vec<_>>());
+        assert_eq!(last.parents, vec![(n - 1, BigUint::from(n - 1))]);
+
+        let first = Provenance::build(&cards, 1, usize::MAX).unwrap();
+        assert_eq!(first.descendants.len(), n - 1);
+
+        let text = first.to_text();
+        let tree: Vec<&str> = text.lines().skip(4).collect();
+        assert_eq!(tree.len(), n);
+        assert_eq!(tree[MAX_INDENT], format!("{}card 33 x33", "  ".repeat(33)));
+        assert_eq!(
+            tree[n - 1],
+            format!("{}[{}] card {} x{}", "  ".repeat(33), n - 1, n, n)
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let provenance = Provenance::build(&example(), 3, usize::MAX).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&provenance.to_json()).unwrap();
+
+        assert_eq!(json["copies"], "4");
+        assert_eq!(json["parents"][1], json!({ "card": 2, "copies": "2" }));
+        assert_eq!(json["descendants"], json!([4, 5]));
+        assert_eq!(
+            json["wins"][0],
+            json!({ "card": 1, "copies": "1", "wins": [2, 3, 4, 5] })
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = Provenance::build(&example(), 3, usize::MAX)
+            .unwrap()
+            .to_dot();
+
+        assert!(dot.starts_with("digraph cascade {\n"));
+        assert!(dot.contains("  c3 [label=\"card 3\\n4 copies\", style=filled"));
+        assert!(dot.contains("  c1 -> c3;\n"));
+        assert!(dot.contains("  c4 -> c5;\n"));
+        assert!(!dot.contains("c6"));
+        assert!(dot.ends_with("}\n"));
+    }
+}
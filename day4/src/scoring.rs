@@ -0,0 +1,251 @@
+use crate::Card;
+use num_bigint::BigUint;
+use rayon::prelude::*;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+// how a deck of scratchcards is scored, from the matches on each card
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rule {
+    // 1 point for the first match, doubled for every match after it, part 1
+    Doubling,
+    // 1 point per match
+    Linear,
+    // 1, 2, 3, 5, 8, .. points for 1, 2, 3, 4, 5, .. matches
+    Fibonacci,
+    // n matches win a copy of each of the next n cards, scoring the number of cards held, part 2
+    Cascade,
+    // as cascade, with wins past the last card carried on from the first card
+    // a truncated rule: those copies add to the cards held but are never scratched, as their
+    // cards have already been played, so they win nothing more
+    CascadeWrap,
+    // as cascade, with no card winning more than n cards
+    CascadeCap(usize),
+}
+
+impl Rule {
+    pub fn evaluate(&self, cards: &[Card]) -> BigUint {
+        match *self {
+            Rule::Doubling => sum_points(cards, |hits| BigUint::from(1_u32) << (hits - 1)),
+            Rule::Linear => sum_points(cards, BigUint::from),
+            Rule::Fibonacci => sum_points(cards, |hits| {
+                let (mut a, mut b) = (BigUint::from(1_u32), BigUint::from(2_u32));
+                for _ in 1..hits {
+                    let next = &a + &b;
+                    a = std::mem::replace(&mut b, next);
+                }
+                a
+            }),
+            Rule::Cascade => cascade(cards, usize::MAX, false),
+            Rule::CascadeWrap => cascade(cards, usize::MAX, true),
+            Rule::CascadeCap(cap) => cascade(cards, cap, false),
+        }
+    }
+
+    // the most cards one card can win, for the cascades where every copy is scratched
+    pub fn cap(&self) -> Option<usize> {
+        match *self {
+            Rule::Cascade => Some(usize::MAX),
+            Rule::CascadeCap(cap) => Some(cap),
+            _ => None,
+        }
+    }
+}
+
+// positions of the cards that cards[i] wins a copy of, given its number of wins
+// cards are in id order, so that's the run after it with ids above its own up to id + wins
+pub fn won_range(cards: &[Card], i: usize, wins: usize) -> Range<usize> {
+    let id = cards[i].id;
+    let rest = &cards[i + 1..];
+
+    i + 1 + rest.partition_point(|x| x.id <= id)
+        ..i + 1 + rest.partition_point(|x| x.id <= id.saturating_add(wins))
+}
+
+// points for every card with at least one match
+fn sum_points(cards: &[Card], points: impl Fn(usize) -> BigUint + Sync) -> BigUint {
+    cards
+        .par_iter()
+        .filter(|card| card.score > 0)
+        .map(|card| points(card.score))
+        .sum()
+}
+
+// every card is held once, and each card won adds as many copies as there are copies of the
+// card that won it, so one count per card is enough
+fn cascade(cards: &[Card], cap: usize, wraps: bool) -> BigUint {
+    // copies still owed to each card by the cards before it, added where a run of wins starts
+    // and taken off again one past where it ends
+    let mut starts: Vec<BigUint> = vec![BigUint::ZERO; cards.len() + 1];
+    let mut ends: Vec<BigUint> = vec![BigUint::ZERO; cards.len() + 1];
+
+    let mut running = BigUint::ZERO;
+    let mut total = BigUint::ZERO;
+
+    for (i, card) in cards.iter().enumerate() {
+        running += &starts[i];
+        running -= &ends[i];
+
+        let copies = &running + 1_u32;
+        let wins = card.score.min(cap);
+
+        let won = won_range(cards, i, wins);
+        if !won.is_empty() {
+            starts[won.start] += &copies;
+            ends[won.end] += &copies;
+        }
+
+        // the rest are lost, or carry on from id 1 without ever reaching this card, landing only
+        // on the ids the deck holds as forward wins do
+        if wraps {
+            let last_id = cards[cards.len() - 1].id;
+            let past_end = card.id.saturating_add(wins).saturating_sub(last_id);
+            let wrapped = match past_end {
+                0 => 0,
+                _ => cards[..i].partition_point(|x| x.id <= past_end),
+            };
+            total += &copies * wrapped;
+        }
+
+        total += copies;
+    }
+
+    total
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Doubling => write!(f, "doubling"),
+            Rule::Linear => write!(f, "linear"),
+            Rule::Fibonacci => write!(f, "fibonacci"),
+            Rule::Cascade => write!(f, "cascade"),
+            Rule::CascadeWrap => write!(f, "cascade-wrap"),
+            Rule::CascadeCap(cap) => write!(f, "cascade-cap:{}", cap),
+        }
+    }
+}
+
+// Example input: "doubling", "cascade" or "cascade-cap:3"
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "doubling" => Ok(Rule::Doubling),
+            "linear" => Ok(Rule::Linear),
+            "fibonacci" => Ok(Rule::Fibonacci),
+            "cascade" => Ok(Rule::Cascade),
+            "cascade-wrap" => Ok(Rule::CascadeWrap),
+            other => {
+                let cap = other
+                    .strip_prefix("cascade-cap:")
+                    .and_then(|cap| cap.parse::<usize>().ok())
+                    .ok_or_else(|| {
+                        format!(
+                            "unknown rule `{}`, expected doubling, linear, fibonacci, cascade, cascade-wrap or cascade-cap:N",
+                            other
+                        )
+                    })?;
+                Ok(Rule::CascadeCap(cap))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{cards_with_scores, example};
+
+    #[test]
+    fn test_points_rules() {
+        assert_eq!(Rule::Doubling.evaluate(&example()), BigUint::from(13_u32));
+        assert_eq!(Rule::Linear.evaluate(&example()), BigUint::from(9_u32));
+        assert_eq!(Rule::Fibonacci.evaluate(&example()), BigUint::from(10_u32));
+
+        assert_eq!(
+            Rule::Fibonacci.evaluate(&cards_with_scores(&[1, 2, 3, 4, 5, 6])),
+            BigUint::from(1_u32 + 2 + 3 + 5 + 8 + 13)
+        );
+        assert_eq!(
+            Rule::Doubling.evaluate(&cards_with_scores(&[100])),
+            BigUint::from(1_u32) << 99
+        );
+    }
+
+    #[test]
+    fn test_cascade_cap() {
+        assert_eq!(
+            Rule::CascadeCap(1).evaluate(&example()),
+            BigUint::from(16_u32)
+        );
+        assert_eq!(
+            Rule::CascadeCap(0).evaluate(&example()),
+            BigUint::from(6_u32)
+        );
+
+        // a cap above every score changes nothing
+        assert_eq!(
+            Rule::CascadeCap(10).evaluate(&example()),
+            BigUint::from(30_u32)
+        );
+    }
+
+    #[test]
+    fn test_cascade_wrap() {
+        // no card in the example wins past the end
+        assert_eq!(
+            Rule::CascadeWrap.evaluate(&example()),
+            BigUint::from(30_u32)
+        );
+
+        // the last card wins the first two back, which win nothing more
+        assert_eq!(
+            Rule::CascadeWrap.evaluate(&cards_with_scores(&[1, 0, 2])),
+            BigUint::from(1_u32 + 2 + 1 + 2)
+        );
+
+        // and never wins itself
+        assert_eq!(
+            Rule::CascadeWrap.evaluate(&cards_with_scores(&[0, 5])),
+            BigUint::from(1_u32 + 1 + 1)
+        );
+        assert_eq!(Rule::CascadeWrap.evaluate(&[]), BigUint::ZERO);
+
+        // ids 11 to 13 wrap onto 1 to 3, of which only card 1 is in the deck
+        let mut cards = cards_with_scores(&[0, 0, 3]);
+        for (card, id) in cards.iter_mut().zip([1, 5, 10]) {
+            card.id = id;
+        }
+        assert_eq!(
+            Rule::CascadeWrap.evaluate(&cards),
+            BigUint::from(1_u32 + 1 + 1 + 1)
+        );
+
+        // huge lenient ids win up to the top of usize rather than overflowing
+        let mut cards = cards_with_scores(&[3, 0]);
+        cards[0].id = usize::MAX - 1;
+        cards[1].id = usize::MAX;
+        assert_eq!(Rule::CascadeWrap.evaluate(&cards), BigUint::from(3_u32));
+        assert_eq!(Rule::Cascade.evaluate(&cards), BigUint::from(3_u32));
+    }
+
+    #[test]
+    fn test_rule_from_str() {
+        for rule in [
+            Rule::Doubling,
+            Rule::Linear,
+            Rule::Fibonacci,
+            Rule::Cascade,
+            Rule::CascadeWrap,
+            Rule::CascadeCap(3),
+        ] {
+            assert_eq!(rule.to_string().parse::<Rule>(), Ok(rule));
+        }
+
+        assert!("cascade-cap:".parse::<Rule>().is_err());
+        assert!("tripling".parse::<Rule>().is_err());
+    }
+}
@@ -0,0 +1,552 @@
+use crate::rangeset::RangeSet;
+use crate::ListMapping;
+use rayon::prelude::*;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// seeds a worker maps between checks for cancellation
+const CHECK_EVERY: u64 = 1 << 16;
+
+// seconds between progress lines, and progress lines between checkpoints
+const TICK: Duration = Duration::from_secs(1);
+const SAVE_EVERY: u32 = 10;
+
+pub struct Options {
+    pub chunk_size: u64,
+    pub checkpoint: Option<String>,
+    pub progress: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Finished(Option<u64>),
+    Cancelled,
+}
+
+// how far a run got, enough to pick it up again with the same seeds, maps and chunk size
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub seeds: String,
+    pub almanac: u64,
+    pub chunk_size: u64,
+    pub min: Option<u64>,
+    // indices of the chunks already searched
+    pub done: RangeSet<u64>,
+}
+
+impl Checkpoint {
+    // Example input:
+    // "seeds 55..68 79..93\nalmanac 5f0e1d2c3b4a6978\nchunk 1048576\nmin 46\ndone 0..2 3..4\n"
+    pub fn parse(s: &str) -> Result<Checkpoint, String> {
+        let mut seeds: Option<String> = None;
+        let mut almanac: Option<u64> = None;
+        let mut chunk_size: Option<u64> = None;
+        let mut min: Option<u64> = None;
+        let mut done: RangeSet<u64> = RangeSet::new();
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let err = || format!("invalid checkpoint line `{}`", line);
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+
+            match key {
+                "seeds" => seeds = Some(value.to_string()),
+                "almanac" => almanac = Some(u64::from_str_radix(value, 16).map_err(|_| err())?),
+                "chunk" => chunk_size = Some(value.parse().map_err(|_| err())?),
+                "min" => {
+                    min = match value {
+                        "none" => None,
+                        x => Some(x.parse().map_err(|_| err())?),
+                    }
+                }
+                "done" => {
+                    for x in value.split_whitespace() {
+                        let (start, end) = x.split_once("..").ok_or_else(err)?;
+                        done.insert(
+                            start.parse().map_err(|_| err())?..end.parse().map_err(|_| err())?,
+                        );
+                    }
+                }
+                _ => return Err(err()),
+            }
+        }
+
+        Ok(Checkpoint {
+            seeds: seeds.ok_or("checkpoint has no seeds line")?,
+            almanac: almanac.ok_or("checkpoint has no almanac line")?,
+            chunk_size: chunk_size.ok_or("checkpoint has no chunk line")?,
+            min,
+            done,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let min = match self.min {
+            Some(x) => x.to_string(),
+            None => "none".to_string(),
+        };
+        let done: Vec<String> = self
+            .done
+            .iter()
+            .map(|r| format!("{}..{}", r.start, r.end))
+            .collect();
+
+        format!(
+            "seeds {}\nalmanac {:016x}\nchunk {}\nmin {}\ndone {}\n",
+            self.seeds,
+            self.almanac,
+            self.chunk_size,
+            min,
+            done.join(" ")
+        )
+    }
+
+    // written next to the file and moved over it, so a crash never leaves half a checkpoint
+    fn save(&self, path: &str) -> Result<(), String> {
+        let tmp = format!("{}.tmp", path);
+
+        fs::write(&tmp, self.to_text())
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn fingerprint(seeds: &RangeSet<u64>) -> String {
+    let ranges: Vec<String> = seeds
+        .iter()
+        .map(|r| format!("{}..{}", r.start, r.end))
+        .collect();
+    ranges.join(" ")
+}
+
+// FNV-1a over every mapping the seeds go through, so a checkpoint isn't taken up after the maps
+// change or --validate resolves them differently, written out as std's hasher may change
+// between releases
+fn almanac_hash(almanac: &[ListMapping]) -> u64 {
+    let values = almanac.iter().flat_map(|list_mapping| {
+        let mappings = list_mapping.value.iter().flat_map(|m| {
+            [
+                m.source_range.start,
+                m.source_range.end,
+                m.destination_range.start,
+            ]
+        });
+        std::iter::once(list_mapping.value.len() as u64).chain(mappings)
+    });
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for x in std::iter::once(almanac.len() as u64).chain(values) {
+        for byte in x.to_le_bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+// the seed ranges cut into runs of at most size seeds, each found from its index so neither
+// the seeds nor the chunks are ever listed
+pub struct Chunks<'a> {
+    seeds: &'a RangeSet<u64>,
+    size: u64,
+    // (chunks, seeds) before each range
+    before: Vec<(u64, u64)>,
+    len: u64,
+    total: u64,
+}
+
+impl<'a> Chunks<'a> {
+    pub fn new(seeds: &'a RangeSet<u64>, size: u64) -> Chunks<'a> {
+        let (mut len, mut total) = (0, 0);
+        let mut before: Vec<(u64, u64)> = Vec::new();
+
+        for r in seeds {
+            before.push((len, total));
+            len += (r.end - r.start).div_ceil(size);
+            total += r.end - r.start;
+        }
+
+        Chunks {
+            seeds,
+            size,
+            before,
+            len,
+            total,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    // seeds in the chunks before chunk i, all of them for i == len
+    fn offset(&self, i: u64) -> u64 {
+        if i >= self.len {
+            return self.total;
+        }
+        let k = self.before.partition_point(|&(chunks, _)| chunks <= i) - 1;
+        let (chunks, seeds) = self.before[k];
+
+        seeds + (i - chunks) * self.size
+    }
+
+    pub fn get(&self, i: u64) -> Range<u64> {
+        let k = self.before.partition_point(|&(chunks, _)| chunks <= i) - 1;
+        let r = &self.seeds.as_slice()[k];
+        let start = r.start + (i - self.before[k].0) * self.size;
+
+        start..r.end.min(start.saturating_add(self.size))
+    }
+
+    // seeds in the chunks with indices in r
+    pub fn seeds_in(&self, r: &Range<u64>) -> u64 {
+        self.offset(r.end) - self.offset(r.start)
+    }
+}
+
+// the lowest location of any seed in chunk, mapping the seeds one by one, None when cancelled
+// before the end
+fn chunk_min(
+    chunk: &Range<u64>,
+    almanac: &[ListMapping],
+    processed: &AtomicU64,
+    cancelled: &AtomicBool,
+) -> Option<u64> {
+    let mut result = u64::MAX;
+    let mut start = chunk.start;
+
+    while start < chunk.end {
+        if cancelled.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let end = chunk.end.min(start.saturating_add(CHECK_EVERY));
+        for seed in start..end {
+            result = result.min(almanac.iter().fold(seed, |x, map| map.map(x)));
+        }
+
+        processed.fetch_add(end - start, Ordering::Relaxed);
+        start = end;
+    }
+
+    Some(result)
+}
+
+// every seed mapped on its own across all cores, chunks finished before a cancellation are kept
+// in the checkpoint, when there is one, and skipped when it's read back
+pub fn run(
+    seeds: &RangeSet<u64>,
+    almanac: &[ListMapping],
+    options: &Options,
+    cancelled: &AtomicBool,
+) -> Result<Outcome, String> {
+    if options.chunk_size == 0 {
+        return Err("chunk size must be at least 1".to_string());
+    }
+    let chunks = Chunks::new(seeds, options.chunk_size);
+
+    let checkpoint = match options.checkpoint.as_deref() {
+        Some(path) if Path::new(path).exists() => {
+            let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+            let checkpoint = Checkpoint::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+
+            if checkpoint.seeds != fingerprint(seeds)
+                || checkpoint.chunk_size != options.chunk_size
+                || checkpoint.done.iter().any(|r| r.end > chunks.len())
+            {
+                return Err(format!(
+                    "{}: checkpoint is for other seeds or another chunk size",
+                    path
+                ));
+            }
+            if checkpoint.almanac != almanac_hash(almanac) {
+                return Err(format!(
+                    "{}: checkpoint is for other maps or another --validate mode",
+                    path
+                ));
+            }
+            checkpoint
+        }
+        _ => Checkpoint {
+            seeds: fingerprint(seeds),
+            almanac: almanac_hash(almanac),
+            chunk_size: options.chunk_size,
+            min: None,
+            done: RangeSet::new(),
+        },
+    };
+
+    let total: u64 = chunks.seeds_in(&(0..chunks.len()));
+    let skipped: u64 = checkpoint.done.iter().map(|r| chunks.seeds_in(r)).sum();
+    // what was done before this run, the checkpoint itself goes on filling up
+    let done_before = checkpoint.done.clone();
+
+    let processed = AtomicU64::new(0);
+    let finished = AtomicBool::new(false);
+    let state = Mutex::new(checkpoint);
+
+    std::thread::scope(|scope| {
+        if options.progress || options.checkpoint.is_some() {
+            scope.spawn(|| {
+                let started = Instant::now();
+                let mut ticks = 0;
+
+                while !finished.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(50));
+                    if started.elapsed() < TICK * (ticks + 1) {
+                        continue;
+                    }
+                    ticks += 1;
+
+                    let state = state.lock().unwrap();
+                    if options.progress {
+                        let done = processed.load(Ordering::Relaxed);
+                        // padded so a shorter line covers the one before it
+                        eprint!(
+                            "\r{:<72}",
+                            progress(done, skipped, total, started.elapsed(), state.min)
+                        );
+                    }
+                    if let Some(path) = options.checkpoint.as_deref() {
+                        if ticks % SAVE_EVERY == 0 {
+                            // a failed save is tried again next time and reported at the end
+                            let _ = state.save(path);
+                        }
+                    }
+                }
+            });
+        }
+
+        let todo = (0..chunks.len())
+            .into_par_iter()
+            .filter(|&i| !done_before.contains(i));
+        todo.for_each(|i| {
+            if let Some(x) = chunk_min(&chunks.get(i), almanac, &processed, cancelled) {
+                let mut state = state.lock().unwrap();
+                state.done.insert(i..i + 1);
+                state.min = Some(state.min.map_or(x, |min| min.min(x)));
+            }
+        });
+
+        finished.store(true, Ordering::Relaxed);
+    });
+
+    if options.progress {
+        eprintln!();
+    }
+
+    let state = state.into_inner().unwrap();
+    if let Some(path) = options.checkpoint.as_deref() {
+        state.save(path)?;
+    }
+
+    match cancelled.load(Ordering::Relaxed) && state.done != RangeSet::from(0..chunks.len()) {
+        true => Ok(Outcome::Cancelled),
+        false => Ok(Outcome::Finished(state.min)),
+    }
+}
+
+// Example output: "1200000/3000000 seeds, 400000/s, eta 0:00:04, min 46"
+fn progress(
+    processed: u64,
+    skipped: u64,
+    total: u64,
+    elapsed: Duration,
+    min: Option<u64>,
+) -> String {
+    let rate = processed as f64 / elapsed.as_secs_f64().max(1e-9);
+    let left = total - skipped - processed.min(total - skipped);
+
+    let eta = match rate > 0.0 {
+        true => {
+            let secs = (left as f64 / rate) as u64;
+            format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+        }
+        false => "?".to_string(),
+    };
+    let min = match min {
+        Some(x) => x.to_string(),
+        None => "none yet".to_string(),
+    };
+
+    format!(
+        "{}/{} seeds, {:.0}/s, eta {}, min {}",
+        skipped + processed,
+        total,
+        rate,
+        eta,
+        min
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn example() -> (RangeSet<u64>, Vec<ListMapping>) {
+        (fixtures::example_seed_ranges(), fixtures::example().1)
+    }
+
+    fn options(chunk_size: u64, checkpoint: Option<String>) -> Options {
+        Options {
+            chunk_size,
+            checkpoint,
+            progress: false,
+        }
+    }
+
+    // a fresh path in the temp directory for each test
+    fn checkpoint_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("day5-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    fn all(chunks: &Chunks) -> Vec<Range<u64>> {
+        (0..chunks.len()).map(|i| chunks.get(i)).collect()
+    }
+
+    #[test]
+    fn test_chunks() {
+        let seeds: RangeSet<u64> = vec![79..93, 55..68].into_iter().collect();
+
+        let chunks = Chunks::new(&seeds, 5);
+        assert_eq!(
+            all(&chunks),
+            vec![55..60, 60..65, 65..68, 79..84, 84..89, 89..93]
+        );
+        assert_eq!(chunks.seeds_in(&(0..6)), 27);
+        assert_eq!(chunks.seeds_in(&(2..4)), 8);
+        assert_eq!(all(&Chunks::new(&seeds, 100)), vec![55..68, 79..93]);
+        assert_eq!(Chunks::new(&RangeSet::new(), 5).len(), 0);
+
+        // one seed a chunk costs nothing up front
+        let seeds = RangeSet::from(0..u64::MAX);
+        let chunks = Chunks::new(&seeds, 1);
+        assert_eq!(chunks.len(), u64::MAX);
+        assert_eq!(chunks.get(u64::MAX - 1), u64::MAX - 1..u64::MAX);
+        assert_eq!(chunks.seeds_in(&(10..20)), 10);
+    }
+
+    #[test]
+    fn test_almanac_hash() {
+        let (_, almanac) = example();
+        let mut other = almanac.clone();
+        other[3].value[0].destination_range.start += 1;
+
+        assert_eq!(almanac_hash(&almanac), almanac_hash(&almanac.clone()));
+        assert_ne!(almanac_hash(&almanac), almanac_hash(&other));
+        assert_ne!(almanac_hash(&almanac), almanac_hash(&almanac[1..]));
+    }
+
+    #[test]
+    fn test_run() {
+        let (seeds, almanac) = example();
+        let cancelled = AtomicBool::new(false);
+
+        for chunk_size in [1, 5, 1000] {
+            assert_eq!(
+                run(&seeds, &almanac, &options(chunk_size, None), &cancelled),
+                Ok(Outcome::Finished(Some(46)))
+            );
+        }
+        assert_eq!(
+            run(&RangeSet::new(), &almanac, &options(5, None), &cancelled),
+            Ok(Outcome::Finished(None))
+        );
+        assert!(run(&seeds, &almanac, &options(0, None), &cancelled).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let checkpoint = Checkpoint {
+            seeds: "55..68 79..93".to_string(),
+            almanac: 0x5f0e_1d2c_3b4a_6978,
+            chunk_size: 5,
+            min: Some(46),
+            done: vec![0..2, 4..5].into_iter().collect(),
+        };
+
+        assert_eq!(Checkpoint::parse(&checkpoint.to_text()), Ok(checkpoint));
+        assert_eq!(
+            Checkpoint::parse("seeds 1..2\nalmanac 0\nchunk 5\nmin none\ndone\n")
+                .unwrap()
+                .min,
+            None
+        );
+        assert!(Checkpoint::parse("seeds 1..2\nalmanac 0\nchunk x\n").is_err());
+        assert!(Checkpoint::parse("seeds 1..2\nchunk 5\n").is_err());
+        assert!(Checkpoint::parse("seeds 1..2\nalmanac 0\nchunk 5\ndone 3\n").is_err());
+    }
+
+    #[test]
+    fn test_cancel_and_resume() {
+        let (seeds, almanac) = example();
+        let path = checkpoint_path("resume");
+
+        // cancelled before any chunk is searched, so nothing is kept
+        let cancelled = AtomicBool::new(true);
+        let result = run(
+            &seeds,
+            &almanac,
+            &options(5, Some(path.clone())),
+            &cancelled,
+        );
+        assert_eq!(result, Ok(Outcome::Cancelled));
+
+        let checkpoint = Checkpoint::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(checkpoint.done.is_empty());
+
+        // chunks marked as done are taken on trust, the minimum found in them included
+        let checkpoint = Checkpoint {
+            min: Some(1),
+            done: RangeSet::from(0..5),
+            ..checkpoint
+        };
+        fs::write(&path, checkpoint.to_text()).unwrap();
+
+        let cancelled = AtomicBool::new(false);
+        let result = run(
+            &seeds,
+            &almanac,
+            &options(5, Some(path.clone())),
+            &cancelled,
+        );
+        assert_eq!(result, Ok(Outcome::Finished(Some(1))));
+
+        let checkpoint = Checkpoint::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(checkpoint.done, RangeSet::from(0..6));
+
+        // nor with other maps
+        let mut other = almanac.clone();
+        other[0].value.pop();
+        let err = run(&seeds, &other, &options(5, Some(path.clone())), &cancelled).unwrap_err();
+        assert!(err.ends_with("checkpoint is for other maps or another --validate mode"));
+
+        // with another chunk size the checkpoint doesn't apply
+        assert!(run(
+            &seeds,
+            &almanac,
+            &options(4, Some(path.clone())),
+            &cancelled
+        )
+        .is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_progress() {
+        assert_eq!(
+            progress(100, 50, 1000, Duration::from_secs(2), Some(46)),
+            "150/1000 seeds, 50/s, eta 0:00:17, min 46"
+        );
+        assert_eq!(
+            progress(0, 0, 10, Duration::from_secs(1), None),
+            "0/10 seeds, 0/s, eta ?, min none yet"
+        );
+    }
+}
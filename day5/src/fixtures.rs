@@ -0,0 +1,42 @@
+use crate::graph::CategoryGraph;
+use crate::rangeset::RangeSet;
+use crate::validate::{resolve, Validation};
+use crate::{parse_input, seed_ranges, ListMapping, SeedMode};
+use proptest::prelude::*;
+
+pub const TEST_DATA: &str = include_str!("../input/test_data.txt");
+
+// the example's categories linked by its maps
+pub fn example_graph() -> CategoryGraph {
+    let (_, (_, sections)) = parse_input(TEST_DATA).unwrap();
+    CategoryGraph::build(resolve(sections, Validation::Reject).unwrap()).unwrap()
+}
+
+// the example's seeds and its maps from seed to location
+pub fn example() -> (Vec<u64>, Vec<ListMapping>) {
+    let (_, (seeds, _)) = parse_input(TEST_DATA).unwrap();
+    let graph = example_graph();
+    let chain = graph.chain("seed", "location").unwrap();
+
+    (
+        seeds,
+        chain.into_iter().map(|m| m.list_mapping.clone()).collect(),
+    )
+}
+
+// the example's seeds read as pairs of start and length
+pub fn example_seed_ranges() -> RangeSet<u64> {
+    let (seeds, _) = example();
+    seed_ranges(&seeds, SeedMode::Ranges)
+        .unwrap()
+        .into_iter()
+        .collect()
+}
+
+// a few maps of a few small mappings, overlapping or not
+pub fn almanac() -> impl Strategy<Value = Vec<ListMapping>> {
+    let mapping = (0..60u64, 0..60u64, 0..20u64).prop_map(|(d, s, l)| vec![d, s, l]);
+    let list_mapping = prop::collection::vec(mapping, 0..5).prop_map(ListMapping::from);
+
+    prop::collection::vec(list_mapping, 1..5)
+}
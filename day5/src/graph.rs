@@ -0,0 +1,348 @@
+use crate::rangeset::RangeSet;
+use crate::ListMapping;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+// one map of the almanac with the categories named in its header, "seed-to-soil map:"
+#[derive(Debug, Clone)]
+pub struct CategoryMap {
+    pub source: String,
+    pub destination: String,
+    pub list_mapping: ListMapping,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    DuplicateMap { source: String, destination: String },
+    // the categories around the loop, the first repeated at the end
+    Cycle(Vec<String>),
+    UnknownCategory(String),
+    NoPath { from: String, to: String },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::DuplicateMap {
+                source,
+                destination,
+            } => write!(
+                f,
+                "there is more than one {}-to-{} map",
+                source, destination
+            ),
+            GraphError::Cycle(categories) => {
+                write!(
+                    f,
+                    "the maps loop back on themselves: {}",
+                    categories.join(" -> ")
+                )
+            }
+            GraphError::UnknownCategory(category) => {
+                write!(f, "no map reads or writes category `{}`", category)
+            }
+            GraphError::NoPath { from, to } => {
+                write!(f, "no chain of maps links {} to {}", from, to)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+// which way a map is used on the way between two categories
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Forward,
+    // through the inverse, every value mapping into the range
+    Backward,
+}
+
+// categories linked by the maps between them, in whatever order the almanac lists them
+#[derive(Debug)]
+pub struct CategoryGraph {
+    maps: Vec<CategoryMap>,
+}
+
+impl CategoryGraph {
+    // no two maps may link the same categories, and no chain of maps may lead back to the
+    // category it started from
+    pub fn build(maps: Vec<CategoryMap>) -> Result<CategoryGraph, GraphError> {
+        for (i, map) in maps.iter().enumerate() {
+            if maps[..i]
+                .iter()
+                .any(|m| m.source == map.source && m.destination == map.destination)
+            {
+                return Err(GraphError::DuplicateMap {
+                    source: map.source.clone(),
+                    destination: map.destination.clone(),
+                });
+            }
+        }
+
+        let graph = CategoryGraph { maps };
+
+        let mut done: HashSet<&str> = HashSet::new();
+        for map in &graph.maps {
+            if let Some(cycle) = graph.find_cycle(&map.source, &mut Vec::new(), &mut done) {
+                return Err(GraphError::Cycle(cycle));
+            }
+        }
+
+        Ok(graph)
+    }
+
+    // depth first through the maps out of category, meeting a category still on the path
+    // closes a loop
+    fn find_cycle<'a>(
+        &'a self,
+        category: &'a str,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Option<Vec<String>> {
+        if let Some(i) = path.iter().position(|&c| c == category) {
+            let mut cycle: Vec<String> = path[i..].iter().map(|c| c.to_string()).collect();
+            cycle.push(category.to_string());
+            return Some(cycle);
+        }
+        if !done.insert(category) {
+            return None;
+        }
+
+        path.push(category);
+        for map in self.maps.iter().filter(|m| m.source == category) {
+            if let Some(cycle) = self.find_cycle(&map.destination, path, done) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+
+        None
+    }
+
+    fn check_category(&self, category: &str) -> Result<(), GraphError> {
+        match self
+            .maps
+            .iter()
+            .any(|m| m.source == category || m.destination == category)
+        {
+            true => Ok(()),
+            false => Err(GraphError::UnknownCategory(category.to_string())),
+        }
+    }
+
+    // the fewest maps leading from one category to the other, breadth first
+    // with backwards set maps may also be walked against their direction, forward steps are
+    // tried first so a chain running forwards wins a tie
+    pub fn path(
+        &self,
+        from: &str,
+        to: &str,
+        backwards: bool,
+    ) -> Result<Vec<(&CategoryMap, Direction)>, GraphError> {
+        self.check_category(from)?;
+        self.check_category(to)?;
+
+        // category -> the category before it and the step taken from there
+        let mut previous: HashMap<&str, (&str, &CategoryMap, Direction)> = HashMap::new();
+        let mut queue: VecDeque<&str> = VecDeque::from([from]);
+
+        while let Some(category) = queue.pop_front() {
+            if category == to {
+                break;
+            }
+
+            let forward = self
+                .maps
+                .iter()
+                .filter(|m| m.source == category)
+                .map(|m| (m, Direction::Forward, m.destination.as_str()));
+            let backward = self
+                .maps
+                .iter()
+                .filter(|m| backwards && m.destination == category)
+                .map(|m| (m, Direction::Backward, m.source.as_str()));
+
+            for (map, direction, next) in forward.chain(backward) {
+                if next != from && !previous.contains_key(next) {
+                    previous.insert(next, (category, map, direction));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut result: Vec<(&CategoryMap, Direction)> = Vec::new();
+        let mut category = to;
+        while category != from {
+            let (before, map, direction) =
+                previous.get(category).ok_or_else(|| GraphError::NoPath {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                })?;
+            result.push((map, *direction));
+            category = before;
+        }
+
+        result.reverse();
+        Ok(result)
+    }
+
+    // the maps taking one category to the other, every one used forwards
+    pub fn chain(&self, from: &str, to: &str) -> Result<Vec<&CategoryMap>, GraphError> {
+        Ok(self
+            .path(from, to, false)?
+            .into_iter()
+            .map(|(map, _)| map)
+            .collect())
+    }
+
+    // every value of category to linked to r in category from
+    pub fn convert(
+        &self,
+        from: &str,
+        to: &str,
+        r: &Range<u64>,
+    ) -> Result<RangeSet<u64>, GraphError> {
+        let mut ranges: RangeSet<u64> = RangeSet::from(r.clone());
+
+        for (map, direction) in self.path(from, to, true)? {
+            ranges = ranges
+                .iter()
+                .flat_map(|range| match direction {
+                    Direction::Forward => map.list_mapping.map_range(range),
+                    Direction::Backward => map.list_mapping.preimage(range),
+                })
+                .collect();
+        }
+
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{example_graph, TEST_DATA};
+    use crate::parse_input;
+    use crate::validate::{resolve, Validation};
+
+    fn map(source: &str, destination: &str, v: Vec<Vec<u64>>) -> CategoryMap {
+        CategoryMap {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            list_mapping: ListMapping::from(v),
+        }
+    }
+
+    fn names(maps: &[&CategoryMap]) -> Vec<String> {
+        maps.iter()
+            .map(|m| format!("{}-to-{}", m.source, m.destination))
+            .collect()
+    }
+
+    #[test]
+    fn test_chain() {
+        let graph = example_graph();
+
+        let chain = graph.chain("seed", "location").unwrap();
+        assert_eq!(chain.len(), 7);
+        assert_eq!(chain[0].source, "seed");
+        assert_eq!(chain[6].destination, "location");
+
+        assert_eq!(
+            names(&graph.chain("soil", "water").unwrap()),
+            vec!["soil-to-fertilizer", "fertilizer-to-water"]
+        );
+        assert!(graph.chain("seed", "seed").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_chain_out_of_order() {
+        // listed backwards, still chained from seed to location
+        let (_, (_, mut sections)) = parse_input(TEST_DATA).unwrap();
+        sections.reverse();
+        let maps = resolve(sections, Validation::Reject).unwrap();
+        let graph = CategoryGraph::build(maps).unwrap();
+
+        assert_eq!(
+            graph
+                .convert("seed", "location", &(79..80))
+                .unwrap()
+                .as_slice(),
+            vec![82..83]
+        );
+    }
+
+    #[test]
+    fn test_convert() {
+        let graph = example_graph();
+
+        // seed 79 is soil 81, fertilizer 81, water 81, light 74, temperature 78 and humidity 78
+        assert_eq!(
+            graph
+                .convert("soil", "humidity", &(81..82))
+                .unwrap()
+                .as_slice(),
+            vec![78..79]
+        );
+        // and back again, along with whatever else lands on soil 81
+        assert!(graph
+            .convert("humidity", "soil", &(78..79))
+            .unwrap()
+            .iter()
+            .any(|r| r.contains(&81)));
+        assert_eq!(
+            graph
+                .convert("location", "seed", &(46..47))
+                .unwrap()
+                .as_slice(),
+            vec![82..83]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let graph = example_graph();
+        assert_eq!(
+            graph.convert("seed", "colour", &(0..1)).unwrap_err(),
+            GraphError::UnknownCategory("colour".to_string())
+        );
+        assert_eq!(
+            graph.chain("location", "seed").unwrap_err().to_string(),
+            "no chain of maps links location to seed"
+        );
+
+        // soil never leads on to water
+        let graph = CategoryGraph::build(vec![
+            map("seed", "soil", vec![]),
+            map("fertilizer", "water", vec![]),
+        ])
+        .unwrap();
+        assert_eq!(
+            graph.convert("seed", "water", &(0..1)).unwrap_err(),
+            GraphError::NoPath {
+                from: "seed".to_string(),
+                to: "water".to_string()
+            }
+        );
+
+        let err = CategoryGraph::build(vec![
+            map("seed", "soil", vec![]),
+            map("soil", "water", vec![]),
+            map("water", "soil", vec![]),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the maps loop back on themselves: soil -> water -> soil"
+        );
+
+        let err = CategoryGraph::build(vec![
+            map("seed", "soil", vec![]),
+            map("seed", "soil", vec![vec![1, 2, 3]]),
+        ])
+        .unwrap_err();
+        assert_eq!(err.to_string(), "there is more than one seed-to-soil map");
+    }
+}
@@ -1,155 +1,581 @@
+mod brute;
+#[cfg(test)]
+mod fixtures;
+mod graph;
+mod piecewise;
+mod rangeset;
+mod validate;
+
+use brute::{Options, Outcome};
+use clap::{Parser, Subcommand};
+use graph::CategoryGraph;
 use nom::{
-    bytes::complete::{tag, take_until, take_while},
-    character::complete::{digit1, line_ending, multispace1, space1},
-    combinator::map_res,
-    multi::separated_list1,
-    sequence::terminated,
+    bytes::complete::tag,
+    character::complete::{alpha1, digit1, line_ending, multispace0, space1},
+    combinator::{cut, map_res, opt},
+    error::ErrorKind,
+    multi::{many0, many1, separated_list1},
+    sequence::preceded,
     IResult,
 };
-use std::{fs, ops::Range};
+use piecewise::{diff_table, Piecewise};
+use rangeset::RangeSet;
+use std::cell::OnceCell;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, fs, ops::Range};
+use validate::{check, resolve, Row, Section, Validation};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Almanac to read
+    #[arg(long, global = true, default_value = "input/data.txt")]
+    input: String,
+
+    /// Errors in the maps: reject reports them, resolve lets later mappings win overlaps, cuts
+    /// short mappings running past the largest value and drops empty ones
+    #[arg(long, global = true, default_value = "reject")]
+    validate: Validation,
+
+    /// How to read the seeds line: individual takes every number as a seed, as in part 1,
+    /// ranges takes them in pairs of start and length, as in part 2
+    #[arg(
+        long = "seeds",
+        id = "seed_mode",
+        global = true,
+        default_value = "ranges"
+    )]
+    seed_mode: SeedMode,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the lowest location for the seed ranges, the default
+    Solve {
+        /// Search up from the lowest location instead, to cross-check the answer
+        #[arg(long)]
+        backwards: bool,
+    },
+    /// Print the lowest location by mapping every seed on its own across all cores, a slow
+    /// cross-check that can be stopped with ctrl-c and resumed from a checkpoint
+    Brute {
+        /// File recording the chunks already searched, read back when it exists
+        #[arg(long)]
+        checkpoint: Option<String>,
+
+        /// Seeds searched as one piece of work
+        #[arg(long, default_value_t = 1 << 20)]
+        chunk: u64,
+
+        /// Don't print progress
+        #[arg(long)]
+        quiet: bool,
+    },
+    /// Print every map composed into one function from seed to location
+    Table,
+    /// Print the seeds, and the value at every stage in between, landing on locations given
+    /// as 46 or as a range 46..50
+    Trace {
+        #[arg(required = true, value_parser = parse_range)]
+        locations: Vec<Range<u64>>,
+    },
+    /// Print the value at every stage for seeds taken one at a time, with the almanac line of
+    /// each mapping that moved it
+    Follow {
+        #[arg(required = true)]
+        seeds: Vec<u64>,
+    },
+    /// Print the seed range holding the seed that lands on the lowest location, and that seed
+    /// followed to it
+    Best,
+    /// Print where seeds land, each given as 79 or as a range 79..93
+    Lookup {
+        #[arg(required = true, value_parser = parse_range)]
+        seeds: Vec<Range<u64>>,
+    },
+    /// Print the seeds that land somewhere else under another almanac
+    Diff {
+        /// Almanac to compare against
+        other: String,
+    },
+    /// Print every overlap, overflow, empty map and pair of meeting source ranges in the
+    /// almanac, with their line numbers
+    Check,
+    /// Print the values of one category linked to values of another, through the fewest maps,
+    /// each given as 42 or as a range 42..50
+    Convert {
+        /// Category the values are in, such as soil
+        #[arg(long)]
+        from: String,
+
+        /// Category to convert them to, such as humidity
+        #[arg(long)]
+        to: String,
+
+        #[arg(required = true, value_parser = parse_range)]
+        values: Vec<Range<u64>>,
+    },
+}
 
 fn main() {
-    let input = read_file("input/data.txt").unwrap();
-    let (_, (seeds, almanac)) = parse_input(&input).unwrap();
-    
-    let seed_ranges = simplify_overlaps(seed_ranges(seeds));
+    let args = Args::parse();
+
+    // read on first use, check reports the errors loading would stop at
+    let loaded: OnceCell<(Vec<u64>, CategoryGraph)> = OnceCell::new();
+    let graph = || &loaded.get_or_init(|| load(&args.input, args.validate)).1;
+    let almanac = || seed_to_location(graph(), &args.input);
+    let seed_ranges = || {
+        let (seeds, _) = loaded.get_or_init(|| load(&args.input, args.validate));
+        seed_ranges(seeds, args.seed_mode).unwrap_or_else(|e| fail(&args.input, e))
+    };
+
+    match args.command.unwrap_or(Command::Solve { backwards: false }) {
+        Command::Solve { backwards } => {
+            let almanac = almanac();
+            let seed_ranges: RangeSet<u64> = seed_ranges().into_iter().collect();
+            let result = match backwards {
+                true => min_location_backwards(&seed_ranges, &almanac),
+                false => min_location(&seed_ranges, &almanac),
+            };
+
+            let result = result.unwrap_or_else(|| fail(&args.input, "no seeds to solve"));
+            println!("{}", result);
+        }
+        Command::Brute {
+            checkpoint,
+            chunk,
+            quiet,
+        } => {
+            static CANCELLED: AtomicBool = AtomicBool::new(false);
+            ctrlc::set_handler(|| CANCELLED.store(true, Ordering::Relaxed))
+                .unwrap_or_else(|e| fail(&args.input, e));
+
+            let options = Options {
+                chunk_size: chunk,
+                checkpoint: checkpoint.clone(),
+                progress: !quiet,
+            };
+            let seed_ranges: RangeSet<u64> = seed_ranges().into_iter().collect();
+            let outcome = brute::run(&seed_ranges, &almanac(), &options, &CANCELLED)
+                .unwrap_or_else(|e| fail(&args.input, e));
+
+            match (outcome, checkpoint) {
+                (Outcome::Finished(result), _) => {
+                    let result = result.unwrap_or_else(|| fail(&args.input, "no seeds to solve"));
+                    println!("{}", result);
+                }
+                (Outcome::Cancelled, Some(path)) => {
+                    eprintln!("cancelled, run again with --checkpoint {} to resume", path);
+                    std::process::exit(130);
+                }
+                (Outcome::Cancelled, None) => {
+                    eprintln!("cancelled");
+                    std::process::exit(130);
+                }
+            }
+        }
+        Command::Trace { locations } => {
+            let categories = stage_names(graph(), &args.input);
 
-    dbg!(seed_ranges.clone());
+            for r in locations {
+                println!("location {}..{}", r.start, r.end);
+                for (category, stage) in categories.iter().zip(trace_back(&r, &almanac())) {
+                    println!("  {}: {}", category, format_ranges(&stage));
+                }
+            }
+        }
+        Command::Follow { seeds } => {
+            let names = stage_names(graph(), &args.input);
+            let almanac = almanac();
 
-    let mut min_location: u64 = u64::MAX;
-    let mut prev_min_location = u64::MAX;
+            for seed in seeds {
+                print_trace(seed, &names, &almanac);
+            }
+        }
+        Command::Best => {
+            let almanac = almanac();
+            let (r, seed) = best_seed(&seed_ranges(), &almanac)
+                .unwrap_or_else(|| fail(&args.input, "no seeds"));
 
-    for seed in seed_ranges.iter().flat_map(|range| range.start..range.end).collect::<Vec<u64>>() {
-        let mut traverser = seed;
+            println!("seeds {}..{} hold the lowest location", r.start, r.end);
+            print_trace(seed, &stage_names(graph(), &args.input), &almanac);
+        }
+        Command::Table => print!("{}", Piecewise::compose(&almanac()).to_table()),
+        Command::Lookup { seeds } => {
+            let f = Piecewise::compose(&almanac());
 
-        for map in &almanac {
-            traverser = map.map(traverser);
+            for r in seeds {
+                let images = format_ranges(&f.apply_range(&r));
+                println!("{}..{} -> {}", r.start, r.end, images);
+            }
         }
+        Command::Diff { other } => {
+            let (_, other_graph) = load(&other, args.validate);
+            let before = Piecewise::compose(&almanac());
+            let after = Piecewise::compose(&seed_to_location(&other_graph, &other));
 
-        min_location = min_location.min(traverser);
-        
-        if min_location < prev_min_location {
-            dbg!(min_location.clone());
-            prev_min_location = min_location;
+            print!("{}", diff_table(&before.diff(&after)));
         }
+        Command::Check => {
+            let input = read_file(&args.input).unwrap_or_else(|e| fail(&args.input, e));
+            let (_, sections) = parse(&input).unwrap_or_else(|e| fail(&args.input, e));
 
+            for issue in check(&sections) {
+                let severity = match issue.is_error() {
+                    true => "error",
+                    false => "note",
+                };
+                println!("{}: {}", severity, issue);
+            }
+        }
+        Command::Convert { from, to, values } => {
+            for r in values {
+                let result = graph()
+                    .convert(&from, &to, &r)
+                    .unwrap_or_else(|e| fail(&args.input, e));
+                let result = format_ranges(&result);
+                println!("{} {}..{} -> {} {}", from, r.start, r.end, to, result);
+            }
+        }
     }
+}
 
-    // // process this one range at a time
-    // for range in seed_ranges {
-    //     let mut traverser: Vec<Range<u64>> = vec![range.clone()];
+fn fail(filepath: &str, e: impl fmt::Display) -> ! {
+    eprintln!("{}: {}", filepath, e);
+    std::process::exit(1);
+}
+
+fn load(filepath: &str, mode: Validation) -> (Vec<u64>, CategoryGraph) {
+    let input = read_file(filepath).unwrap_or_else(|e| fail(filepath, e));
+    let (seeds, sections) = parse(&input).unwrap_or_else(|e| fail(filepath, e));
 
-    //     for list_mapping in &almanac {
-    //         let mut output: Vec<Range<u64>> = Vec::new(); 
-            
-    //         for range in traverser.iter() {
-    //             output.extend(list_mapping.map_range(&range));
-    //         }
-    
-    //         // update the whole range after going through a map
-    //         traverser = output;
+    let maps = resolve(sections, mode).unwrap_or_else(|errors| {
+        for e in errors {
+            eprintln!("{}: {}", filepath, e);
+        }
+        std::process::exit(1);
+    });
 
-    //         dbg!(traverser.clone());
-    //     }
+    (
+        seeds,
+        CategoryGraph::build(maps).unwrap_or_else(|e| fail(filepath, e)),
+    )
+}
 
-    //     dbg!(min_start(traverser.clone()));
+// the whole input, anything left over is reported with the line it's on
+fn parse(input: &str) -> Result<(Vec<u64>, Vec<Section>), String> {
+    match parse_input(input) {
+        Ok((rest, result)) if rest.trim().is_empty() => Ok(result),
+        Ok((rest, _)) => Err(format!(
+            "line {}: unexpected `{}`",
+            line_number(input, rest),
+            rest.lines().next().unwrap_or_default()
+        )),
+        // only a number on the seeds line fails to convert, past the seeds a row that doesn't
+        // parse is left over instead
+        Err(nom::Err::Failure(e)) if e.code == ErrorKind::MapRes => {
+            let digits: String = e.input.chars().take_while(|c| c.is_ascii_digit()).collect();
+            Err(format!(
+                "line {}: seed {} does not fit in u64",
+                line_number(input, e.input),
+                digits
+            ))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
 
-    //     min_location = min_location.min(min_start(traverser));
+// the categories from seed to location, in the order the maps reach them
+fn stage_names(graph: &CategoryGraph, filepath: &str) -> Vec<String> {
+    let chain = graph
+        .chain("seed", "location")
+        .unwrap_or_else(|e| fail(filepath, e));
 
-    //     println!("{}", format!("{:?} done", range));
-    // } 
+    std::iter::once("seed".to_string())
+        .chain(chain.iter().map(|m| m.destination.clone()))
+        .collect()
+}
 
-    println!("{}", min_location);
+fn print_trace(seed: u64, names: &[String], almanac: &[ListMapping]) {
+    println!("{} {}", names[0], seed);
+    for (name, (x, mapping)) in names[1..].iter().zip(trace(seed, almanac)) {
+        match mapping {
+            Some(mapping) => println!("  {} {}, line {}: {}", name, x, mapping.line, mapping),
+            None => println!("  {} {}, unchanged", name, x),
+        }
+    }
 }
 
-fn seed_ranges(v: Vec<u64>) -> Vec<Range<u64>> {
-    v.chunks_exact(2)
+// the maps from seed to location, in the order they're applied
+fn seed_to_location(graph: &CategoryGraph, filepath: &str) -> Vec<ListMapping> {
+    graph
+        .chain("seed", "location")
+        .unwrap_or_else(|e| fail(filepath, e))
         .into_iter()
-        .map(|x| x[0]..(x[0] + x[1]))
-        .collect::<Vec<Range<u64>>>()
+        .map(|map| map.list_mapping.clone())
+        .collect()
 }
 
-fn min_start(v: Vec<Range<u64>>) -> u64 {
-    v.iter().map(|range| range.start).min().unwrap()
+fn format_ranges(set: &RangeSet<u64>) -> String {
+    let ranges: Vec<String> = set
+        .iter()
+        .map(|r| format!("{}..{}", r.start, r.end))
+        .collect();
+    ranges.join(", ")
 }
 
-fn simplify_overlaps(mut v: Vec<Range<u64>>) -> Vec<Range<u64>> {
-    v.sort_by(|a, b| a.start.cmp(&b.start));
+// Example input: "79" for that value alone or "79..93" for a half open range
+fn parse_range(s: &str) -> Result<Range<u64>, String> {
+    let err = |_| format!("invalid value `{}`, expected N or N..M", s);
 
-    let mut result = Vec::new();
+    match s.split_once("..") {
+        Some((start, end)) => Ok(start.parse().map_err(err)?..end.parse().map_err(err)?),
+        None => {
+            let x: u64 = s.parse().map_err(err)?;
+            x.checked_add(1)
+                .map(|end| x..end)
+                .ok_or_else(|| format!("value `{}` is too large", s))
+        }
+    }
+}
 
-    let mut current_range = v[0].clone();
+// how the numbers on the seeds line are read
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SeedMode {
+    Individual,
+    Ranges,
+}
 
-    for range in v.into_iter().skip(1) {
-        if range.start <= current_range.end {
-            // Ranges overlap, so merge them
-            current_range.end = current_range.end.max(range.end);
-        } else {
-            // Ranges don't overlap, so add the current range to the result and start a new one
-            result.push(current_range);
-            current_range = range;
+impl fmt::Display for SeedMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeedMode::Individual => write!(f, "individual"),
+            SeedMode::Ranges => write!(f, "ranges"),
         }
     }
+}
+
+// Example input: "individual" or "ranges"
+impl FromStr for SeedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "individual" => Ok(SeedMode::Individual),
+            "ranges" => Ok(SeedMode::Ranges),
+            other => Err(format!(
+                "unknown seed mode `{}`, expected individual or ranges",
+                other
+            )),
+        }
+    }
+}
+
+// the seeds as ranges in the order the seeds line gives them, a single seed is a range of one
+fn seed_ranges(seeds: &[u64], mode: SeedMode) -> Result<Vec<Range<u64>>, String> {
+    let range = |start: u64, length: u64| {
+        start
+            .checked_add(length)
+            .map(|end| start..end)
+            .ok_or_else(|| format!("line 1: seeds {} {} run past {}", start, length, u64::MAX))
+    };
+
+    match mode {
+        SeedMode::Individual => seeds.iter().map(|&seed| range(seed, 1)).collect(),
+        SeedMode::Ranges => match seeds.len() % 2 {
+            0 => seeds.chunks_exact(2).map(|x| range(x[0], x[1])).collect(),
+            _ => Err(format!(
+                "line 1: {} seeds can't be read as pairs of start and length",
+                seeds.len()
+            )),
+        },
+    }
+}
+
+// push whole ranges of seeds through every map, splitting them wherever a mapping starts or
+// ends, so the work depends on the number of ranges rather than the number of seeds
+fn min_location(seed_ranges: &RangeSet<u64>, almanac: &[ListMapping]) -> Option<u64> {
+    let mut traverser: RangeSet<u64> = seed_ranges.clone();
+
+    for list_mapping in almanac {
+        traverser = traverser
+            .iter()
+            .flat_map(|range| list_mapping.map_range(range))
+            .collect();
+    }
 
-    // Add the last range to the result
-    result.push(current_range);
+    traverser.min()
+}
 
-    result
+// the value at every stage after seed, with the mapping that moved it there
+fn trace(seed: u64, almanac: &[ListMapping]) -> Vec<(u64, Option<&Mapping>)> {
+    almanac
+        .iter()
+        .scan(seed, |x, list_mapping| {
+            let step = list_mapping.step(*x);
+            *x = step.0;
+            Some(step)
+        })
+        .collect()
 }
 
+// the range from the seeds line holding the seed that lands lowest, and that seed, the
+// smallest one when several land there
+fn best_seed(seeds: &[Range<u64>], almanac: &[ListMapping]) -> Option<(Range<u64>, u64)> {
+    let seed_ranges: RangeSet<u64> = seeds.iter().cloned().collect();
+    let location = min_location(&seed_ranges, almanac)?;
+    let seed = trace_back(&(location..location + 1), almanac)[0]
+        .intersection(&seed_ranges)
+        .min()?;
+
+    seeds
+        .iter()
+        .find(|r| r.contains(&seed))
+        .map(|r| (r.clone(), seed))
+}
+
+// the values at every stage that end up in locations, seeds first and locations last
+fn trace_back(locations: &Range<u64>, almanac: &[ListMapping]) -> Vec<RangeSet<u64>> {
+    let mut stages: Vec<RangeSet<u64>> = vec![RangeSet::from(locations.clone())];
+
+    for list_mapping in almanac.iter().rev() {
+        let previous = stages[stages.len() - 1]
+            .iter()
+            .flat_map(|range| list_mapping.preimage(range))
+            .collect();
+        stages.push(previous);
+    }
+
+    stages.reverse();
+    stages
+}
+
+// the same answer as min_location found from the other end, the smallest n for which some
+// seed lands below n, binary searched rather than trying locations one by one
+// a seed landing on u64::MAX itself is never found
+fn min_location_backwards(seed_ranges: &RangeSet<u64>, almanac: &[ListMapping]) -> Option<u64> {
+    let reaches = |n: u64| {
+        !trace_back(&(0..n), almanac)[0]
+            .intersection(seed_ranges)
+            .is_empty()
+    };
+
+    if !reaches(u64::MAX) {
+        return None;
+    }
+
+    // nothing lands below 0 and something lands below u64::MAX
+    let (mut low, mut high) = (0, u64::MAX);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        match reaches(mid) {
+            true => high = mid,
+            false => low = mid,
+        }
+    }
+
+    Some(high - 1)
+}
+
+// one map of the almanac, every source range is half open, start included and end excluded
+// a value is moved by exactly one mapping, the last listed whose source range holds it, or
+// kept as it is when none does
 #[derive(Debug, Clone)]
 struct ListMapping {
     value: Vec<Mapping>,
 }
 
 impl ListMapping {
+    // unchecked, almanacs read from a file go through validate::resolve instead
+    #[cfg(test)]
     fn from(v: Vec<Vec<u64>>) -> ListMapping {
-        let value = v
-            .into_iter()
-            .map(|v| Mapping::from(v))
-            .collect::<Vec<Mapping>>();
+        let value = v.into_iter().map(Mapping::from).collect::<Vec<Mapping>>();
 
         ListMapping { value }
     }
 
+    // one seed at a time, for the brute force and to check map_range against
     fn map(&self, seed: u64) -> u64 {
-        // set default to original seed value
-        let mut mapped_value = seed.clone();
+        self.step(seed).0
+    }
 
-        for mapping in &self.value {
-            if mapping.source_range.contains(&seed) {
-                mapped_value =
-                    mapping.destination_range.start + (&seed - mapping.source_range.start)
-            }
+    // the value x maps to and the mapping that moved it, None when it's kept as it is
+    fn step(&self, x: u64) -> (u64, Option<&Mapping>) {
+        match self
+            .value
+            .iter()
+            .rev()
+            .find(|m| m.source_range.contains(&x))
+        {
+            Some(mapping) => (mapping.map(x), Some(mapping)),
+            None => (x, None),
         }
-        mapped_value
     }
-    fn map_range(&self, r: &Range<u64>) -> Vec<Range<u64>> {
+
+    // the image of a range
+    fn map_range(&self, r: &Range<u64>) -> RangeSet<u64> {
         let mut output: Vec<Range<u64>> = Vec::new();
+        let mut unmapped: Vec<Range<u64>> = vec![r.clone()];
+
+        // later mappings win where sources overlap, the same as `map`, so each one in turn
+        // claims what the ones after it left unmapped
+        for mapping in self.value.iter().rev() {
+            let mut rest: Vec<Range<u64>> = Vec::new();
+
+            for piece in unmapped {
+                let (mapped, outside) = mapping.split(&piece);
+                output.extend(mapped);
+                rest.extend(outside);
+            }
 
-        for map in &self.value {
-            output.extend(map.map_range(r));
+            unmapped = rest;
         }
 
-        output.sort_by(|a, b| a.start.cmp(&b.start));
-        output.dedup();
-        simplify_overlaps(output)
+        // whatever no mapping covers keeps its value
+        output.extend(unmapped);
+        output.into_iter().collect()
     }
 
+    // every value landing in r
+    fn preimage(&self, r: &Range<u64>) -> RangeSet<u64> {
+        let mut result: RangeSet<u64> = RangeSet::new();
+
+        // a mapping only moves what no later mapping claims
+        for (i, mapping) in self.value.iter().enumerate() {
+            if let Some(source) = mapping.preimage(r) {
+                let claimed: RangeSet<u64> = self.value[i + 1..]
+                    .iter()
+                    .map(|m| m.source_range.clone())
+                    .collect();
+                result = result.union(&RangeSet::from(source).difference(&claimed));
+            }
+        }
+
+        // and whatever no mapping holds lands on itself
+        let sources: RangeSet<u64> = self.value.iter().map(|m| m.source_range.clone()).collect();
+        result.union(&RangeSet::from(r.clone()).difference(&sources))
+    }
 }
 
 #[derive(Debug, Clone)]
 struct Mapping {
     source_range: Range<u64>,
     destination_range: Range<u64>,
+    // line of the almanac it was read from, 0 when made up in a test
+    line: usize,
 }
 
 impl Mapping {
+    #[cfg(test)]
     fn from(v: Vec<u64>) -> Mapping {
         if v.len() == 3 {
             Mapping {
                 source_range: v[1]..(v[1] + v[2]),
                 destination_range: v[0]..(v[0] + v[2]),
+                line: 0,
             }
         } else {
             unimplemented!();
@@ -164,86 +590,151 @@ impl Mapping {
         }
     }
 
-    fn map_range(&self, r: &Range<u64>) -> Vec<Range<u64>> {
-        let (i_start, i_end, j_start, j_end) = (
-            r.start,
-            r.end,
-            self.source_range.start,
-            self.source_range.end,
-        );
-    
-        if self.source_range.contains(&i_start) && self.source_range.contains(&i_end) {
-            vec![self.map(i_start)..self.map(i_end)]
-        } else if i_end <= j_start || i_start >= j_end {
-            vec![r.clone()]
-        } else {
-            let overlap_start = i_start.max(j_start);
-            let overlap_end = i_end.min(j_end);
-            let mut result = Vec::new();
-    
-            if i_start < overlap_start {
-                result.push(i_start..overlap_start);
-            }
-    
-            result.push(self.map(overlap_start)..self.map(overlap_end));
-    
-            if i_end > overlap_end {
-                result.push(overlap_end..i_end);
+    // (the mapped image of the part of r inside the source range, the parts of r either side)
+    fn split(&self, r: &Range<u64>) -> (Option<Range<u64>>, Vec<Range<u64>>) {
+        let overlap_start = r.start.max(self.source_range.start);
+        let overlap_end = r.end.min(self.source_range.end);
+
+        if overlap_start >= overlap_end {
+            return (None, vec![r.clone()]);
+        }
+
+        let mapped_start = self.map(overlap_start);
+        let mapped = mapped_start..mapped_start + (overlap_end - overlap_start);
+
+        let outside = [r.start..overlap_start, overlap_end..r.end]
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .collect();
+
+        (Some(mapped), outside)
+    }
+
+    // the part of the source range landing in r
+    fn preimage(&self, r: &Range<u64>) -> Option<Range<u64>> {
+        let start = r.start.max(self.destination_range.start);
+        let end = r.end.min(self.destination_range.end);
+
+        match start < end {
+            true => {
+                let source = |x: u64| self.source_range.start + (x - self.destination_range.start);
+                Some(source(start)..source(end))
             }
-    
-            result.sort_by(|a, b| a.start.cmp(&b.start));
-            result.dedup();
-            simplify_overlaps(result)
+            false => None,
         }
     }
 }
 
+// as the almanac writes it, "50 98 2"
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.destination_range.start,
+            self.source_range.start,
+            self.source_range.end - self.source_range.start
+        )
+    }
+}
+
 fn read_file(filepath: &str) -> std::io::Result<String> {
     fs::read_to_string(filepath)
 }
 
+// a number too large for u64 is a failure rather than the end of the list
 fn parse_seeds(s: &str) -> IResult<&str, Vec<u64>> {
     let (input, _) = tag("seeds: ")(s)?;
-    let (input, v) = separated_list1(space1, digit1)(input)?;
+    let seed = cut(map_res(digit1, |x: &str| x.parse::<u64>()));
+    let (input, v) = separated_list1(space1, seed)(input)?;
     let (input, _) = line_ending(input)?;
 
-    let v: Vec<u64> = v
-        .into_iter()
-        .map(|num| num.parse::<u64>().unwrap())
-        .collect();
-
     Ok((input, v))
 }
 
-fn parse_map(s: &str) -> IResult<&str, Vec<Vec<u64>>> {
-    let (input, _) = terminated(take_until("\n"), line_ending)(s)?;
+// line of full that rest starts on, counted from 1
+fn line_number(full: &str, rest: &str) -> usize {
+    full[..full.len() - rest.len()].matches('\n').count() + 1
+}
 
-    let parse_u64 = |input: &str| input.parse::<u64>();
-    let parse_line = separated_list1(
-        space1,
-        map_res(take_while(|c: char| c.is_digit(10)), parse_u64),
-    );
+// Example input: "seed-to-soil map:\n", giving the source and destination categories
+fn parse_header(s: &str) -> IResult<&str, (&str, &str)> {
+    let (input, source) = alpha1(s)?;
+    let (input, _) = tag("-to-")(input)?;
+    let (input, destination) = alpha1(input)?;
+    let (input, _) = tag(" map:")(input)?;
+    let (input, _) = line_ending(input)?;
 
-    separated_list1(terminated(take_until("\n"), line_ending), parse_line)(input)
+    Ok((input, (source, destination)))
 }
 
-fn parse_input(s: &str) -> IResult<&str, (Vec<u64>, Vec<ListMapping>)> {
-    let (input, seeds) = parse_seeds(s)?;
-    let (input, _) = terminated(take_until("\n"), line_ending)(input)?;
+// Example input: "50 98 2", the destination start, source start and length
+fn parse_row<'a>(full: &'a str) -> impl FnMut(&'a str) -> IResult<&'a str, Row> {
+    move |s: &'a str| {
+        let line = line_number(full, s);
+        let parse_u64 = |input: &str| input.parse::<u64>();
+
+        let (input, destination) = map_res(digit1, parse_u64)(s)?;
+        let (input, _) = space1(input)?;
+        let (input, source) = map_res(digit1, parse_u64)(input)?;
+        let (input, _) = space1(input)?;
+        let (input, length) = map_res(digit1, parse_u64)(input)?;
+        let (input, _) = opt(line_ending)(input)?;
+
+        Ok((
+            input,
+            Row {
+                line,
+                destination,
+                source,
+                length,
+            },
+        ))
+    }
+}
 
-    let (input, v) = separated_list1(multispace1, parse_map)(input)?;
+// a header and its rows, there may be none
+fn parse_map<'a>(full: &'a str) -> impl FnMut(&'a str) -> IResult<&'a str, Section> {
+    move |s: &'a str| {
+        let line = line_number(full, s);
+        let (input, (source, destination)) = parse_header(s)?;
+        let (input, rows) = many0(parse_row(full))(input)?;
 
-    let mut result_vec: Vec<ListMapping> = Vec::new();
-    for entry in v {
-        result_vec.push(ListMapping::from(entry));
+        Ok((
+            input,
+            Section {
+                line,
+                source: source.to_string(),
+                destination: destination.to_string(),
+                rows,
+            },
+        ))
     }
+}
+
+// the maps in the order they're listed, which needn't be the order they're applied in
+fn parse_input(s: &str) -> IResult<&str, (Vec<u64>, Vec<Section>)> {
+    let (input, seeds) = parse_seeds(s)?;
+    let (input, sections) = many1(preceded(multispace0, parse_map(s)))(input)?;
 
-    Ok((input, (seeds, result_vec)))
+    Ok((input, (seeds, sections)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use clap::CommandFactory;
+    use fixtures::{almanac, example, example_seed_ranges, TEST_DATA};
+    use proptest::prelude::*;
+
+    // every seed one at a time
+    fn brute_force(seed_ranges: &[Range<u64>], almanac: &[ListMapping]) -> Option<u64> {
+        seed_ranges
+            .iter()
+            .flat_map(|range| range.clone())
+            .map(|seed| almanac.iter().fold(seed, |x, map| map.map(x)))
+            .min()
+    }
 
     #[test]
     fn test_parse_seeds_line() {
@@ -255,14 +746,46 @@ mod tests {
         assert_eq!(result, expected_output);
     }
 
+    #[test]
+    fn test_parse_seed_too_large() {
+        let input = TEST_DATA.replacen("14", "99999999999999999999", 1);
+
+        assert_eq!(
+            parse(&input).unwrap_err(),
+            "line 1: seed 99999999999999999999 does not fit in u64"
+        );
+        assert!(parse(&TEST_DATA.replacen("14", "18446744073709551615", 1)).is_ok());
+    }
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+
+        // subcommand arguments named like a global option
+        let args = Args::try_parse_from(["day5", "--seeds", "individual", "follow", "79"]).unwrap();
+        assert_eq!(args.seed_mode, SeedMode::Individual);
+        assert!(matches!(args.command, Some(Command::Follow { seeds }) if seeds == [79]));
+        assert!(Args::try_parse_from(["day5", "lookup", "79..93"]).is_ok());
+    }
+
     #[test]
     fn test_parse_map() {
         let input = "seed-to-soil map:\n50 98 2\n52 50 48\n";
-        let expected_output = vec![vec![50, 98, 2], vec![52, 50, 48]];
 
-        let result = parse_map(input).unwrap().1;
+        let result = parse_map(input)(input).unwrap().1;
 
-        assert_eq!(result, expected_output);
+        assert_eq!(result.source, "seed");
+        assert_eq!(result.destination, "soil");
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(
+            (
+                result.rows[1].destination,
+                result.rows[1].source,
+                result.rows[1].length
+            ),
+            (52, 50, 48)
+        );
+        assert_eq!(result.rows[1].line, 3);
     }
 
     #[test]
@@ -271,6 +794,7 @@ mod tests {
         let expected_output = Mapping {
             source_range: 98..100,
             destination_range: 50..52,
+            line: 0,
         };
 
         let result = Mapping::from(input);
@@ -286,10 +810,12 @@ mod tests {
                 Mapping {
                     source_range: Range { start: 10, end: 20 },
                     destination_range: Range { start: 30, end: 40 },
+                    line: 0,
                 },
                 Mapping {
                     source_range: Range { start: 20, end: 30 },
                     destination_range: Range { start: 40, end: 50 },
+                    line: 0,
                 },
             ],
         };
@@ -301,4 +827,388 @@ mod tests {
 
         assert_eq!(result, expected_output);
     }
+
+    #[test]
+    fn test_parse_input() {
+        let (_, (seeds, maps)) = parse_input(TEST_DATA).unwrap();
+
+        assert_eq!(seeds, vec![79, 14, 55, 13]);
+        assert_eq!(maps.len(), 7);
+        assert_eq!(maps[6].rows.len(), 2);
+        assert_eq!(maps[6].source, "humidity");
+        assert_eq!(maps[6].destination, "location");
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header("light-to-temperature map:\n45 77 23\n").unwrap(),
+            ("45 77 23\n", ("light", "temperature"))
+        );
+        assert!(parse_header("light to temperature map:\n").is_err());
+        assert!(parse_header("light-to-temperature:\n").is_err());
+    }
+
+    #[test]
+    fn test_min_location() {
+        let (_, almanac) = example();
+        let seed_ranges = example_seed_ranges();
+
+        assert_eq!(min_location(&seed_ranges, &almanac), Some(46));
+        assert_eq!(min_location(&RangeSet::new(), &almanac), None);
+    }
+
+    #[test]
+    fn test_mapping_split() {
+        let mapping = Mapping::from(vec![50, 98, 2]);
+
+        assert_eq!(
+            mapping.split(&(90..110)),
+            (Some(50..52), vec![90..98, 100..110])
+        );
+        assert_eq!(mapping.split(&(98..99)), (Some(50..51), vec![]));
+        assert_eq!(
+            mapping.split(&(100..110)),
+            (
+                None,
+                vec![Range {
+                    start: 100,
+                    end: 110
+                }]
+            )
+        );
+    }
+
+    #[test]
+    fn test_list_mapping_map_range() {
+        let list_mapping = ListMapping::from(vec![vec![50, 98, 2], vec![52, 50, 48]]);
+
+        // 79..93 sits inside the second mapping
+        assert_eq!(
+            list_mapping.map_range(&(79..93)).as_slice(),
+            vec![Range { start: 81, end: 95 }]
+        );
+
+        // 50..98 shifts up to 52..100 and 98..100 drops to 50..52, filling the gap exactly
+        assert_eq!(
+            list_mapping.map_range(&(0..110)).as_slice(),
+            vec![Range { start: 0, end: 110 }]
+        );
+        assert_eq!(
+            list_mapping.map_range(&(40..52)).as_slice(),
+            vec![40..50, 52..54]
+        );
+    }
+
+    // map_range against mapping every point of the range on its own
+    fn assert_matches_points(list_mapping: &ListMapping, range: Range<u64>) {
+        let expected: RangeSet<u64> = range
+            .clone()
+            .map(|x| list_mapping.map(x))
+            .map(|x| x..x + 1)
+            .collect();
+
+        assert_eq!(
+            list_mapping.map_range(&range),
+            expected,
+            "{:?} through {:?}",
+            range,
+            list_mapping
+        );
+    }
+
+    #[test]
+    fn test_map_range_boundaries() {
+        // source 10..20 moves to 100..110
+        let list_mapping = ListMapping::from(vec![vec![100, 10, 10]]);
+
+        // ending exactly at the source end is wholly inside
+        assert_eq!(
+            list_mapping.map_range(&(10..20)).as_slice(),
+            vec![Range {
+                start: 100,
+                end: 110
+            }]
+        );
+        assert_eq!(
+            list_mapping.map_range(&(15..20)).as_slice(),
+            vec![Range {
+                start: 105,
+                end: 110
+            }]
+        );
+        // starting at the source end is wholly outside
+        assert_eq!(
+            list_mapping.map_range(&(20..25)).as_slice(),
+            vec![Range { start: 20, end: 25 }]
+        );
+        // ending at the source start is wholly outside
+        assert_eq!(
+            list_mapping.map_range(&(5..10)).as_slice(),
+            vec![Range { start: 5, end: 10 }]
+        );
+        // one point either side of the source
+        assert_eq!(
+            list_mapping.map_range(&(9..21)).as_slice(),
+            vec![9..10, 20..21, 100..110]
+        );
+        assert_eq!(list_mapping.map_range(&(19..20)).as_slice(), vec![109..110]);
+        assert!(list_mapping.map_range(&(15..15)).is_empty());
+
+        // a mapping with no length moves nothing
+        let list_mapping = ListMapping::from(vec![vec![100, 10, 0]]);
+        assert_eq!(
+            list_mapping.map_range(&(5..15)).as_slice(),
+            vec![Range { start: 5, end: 15 }]
+        );
+    }
+
+    #[test]
+    fn test_map_range_adjacent_mappings() {
+        // 10..20 -> 100..110 and 20..30 -> 0..10, meeting at 20
+        let list_mapping = ListMapping::from(vec![vec![100, 10, 10], vec![0, 20, 10]]);
+
+        assert_eq!(
+            list_mapping.map_range(&(19..21)).as_slice(),
+            vec![0..1, 109..110]
+        );
+        assert_eq!(
+            list_mapping.map_range(&(10..30)).as_slice(),
+            vec![0..10, 100..110]
+        );
+        assert_eq!(
+            list_mapping.map_range(&(5..35)).as_slice(),
+            vec![0..10, 30..35, 100..110]
+        );
+    }
+
+    #[test]
+    fn test_map_range_overlapping_mappings() {
+        // both claim 15..20, the later one wins there
+        let list_mapping = ListMapping::from(vec![vec![100, 10, 10], vec![200, 15, 10]]);
+
+        assert_eq!(list_mapping.map(17), 202);
+        assert_eq!(
+            list_mapping.map_range(&(10..25)).as_slice(),
+            vec![100..105, 200..210]
+        );
+    }
+
+    #[test]
+    fn test_map_range_exhaustive_single() {
+        // every mapping with its source and destination within 0..12, against every range
+        for source in 0..6 {
+            for length in 0..6 {
+                for destination in 0..8 {
+                    let list_mapping = ListMapping::from(vec![vec![destination, source, length]]);
+
+                    for start in 0..12 {
+                        for end in start..12 {
+                            assert_matches_points(&list_mapping, start..end);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_map_range_exhaustive_pairs() {
+        // two mappings side by side, overlapping or apart, against every range
+        for first_length in 0..4 {
+            for second_source in 0..8 {
+                for second_length in 0..4 {
+                    let list_mapping = ListMapping::from(vec![
+                        vec![20, 2, first_length],
+                        vec![1, second_source, second_length],
+                    ]);
+
+                    for start in 0..12 {
+                        for end in start..12 {
+                            assert_matches_points(&list_mapping, start..end);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mapping_preimage() {
+        let mapping = Mapping::from(vec![50, 98, 2]);
+
+        assert_eq!(mapping.preimage(&(40..51)), Some(98..99));
+        assert_eq!(mapping.preimage(&(50..52)), Some(98..100));
+        assert_eq!(mapping.preimage(&(52..60)), None);
+    }
+
+    #[test]
+    fn test_list_mapping_preimage() {
+        let list_mapping = ListMapping::from(vec![vec![50, 98, 2], vec![52, 50, 48]]);
+
+        // 50 and 51 come from 98 and 99 as well as from themselves, 52 only from 50
+        assert_eq!(
+            list_mapping.preimage(&(50..53)).as_slice(),
+            vec![50..51, 98..100]
+        );
+        assert_eq!(
+            list_mapping.preimage(&(100..101)).as_slice(),
+            vec![100..101]
+        );
+
+        // 5 is claimed by the later mapping, so nothing lands on 15
+        let list_mapping = ListMapping::from(vec![vec![15, 5, 1], vec![25, 5, 1]]);
+        assert_eq!(list_mapping.preimage(&(15..16)).as_slice(), vec![15..16]);
+        assert_eq!(
+            list_mapping.preimage(&(25..26)).as_slice(),
+            vec![5..6, 25..26]
+        );
+    }
+
+    #[test]
+    fn test_trace_back() {
+        let (_, almanac) = example();
+        let stages = trace_back(&(46..47), &almanac);
+
+        // seed 82 goes through soil 84, fertilizer 84, water 84, light 77, temperature 45 and
+        // humidity 46 on its way to location 46
+        let through_82: Vec<u64> = [82, 84, 84, 84, 77, 45, 46, 46].to_vec();
+        assert_eq!(stages.len(), 8);
+        for (stage, x) in stages.iter().zip(through_82) {
+            assert!(stage.iter().any(|range| range.contains(&x)));
+        }
+    }
+
+    #[test]
+    fn test_seed_ranges() {
+        let seeds = [79, 14, 55, 13];
+
+        assert_eq!(
+            seed_ranges(&seeds, SeedMode::Ranges),
+            Ok(vec![79..93, 55..68])
+        );
+        assert_eq!(
+            seed_ranges(&seeds, SeedMode::Individual),
+            Ok(vec![79..80, 14..15, 55..56, 13..14])
+        );
+
+        // the odd one out used to be dropped without a word
+        assert_eq!(
+            seed_ranges(&[79, 14, 55], SeedMode::Ranges),
+            Err("line 1: 3 seeds can't be read as pairs of start and length".to_string())
+        );
+        assert!(seed_ranges(&[u64::MAX - 1, 2], SeedMode::Ranges).is_err());
+        assert!(seed_ranges(&[u64::MAX], SeedMode::Individual).is_err());
+        assert_eq!(
+            seed_ranges(&[u64::MAX - 1, 1], SeedMode::Ranges),
+            Ok(vec![Range {
+                start: u64::MAX - 1,
+                end: u64::MAX
+            }])
+        );
+
+        for mode in [SeedMode::Individual, SeedMode::Ranges] {
+            assert_eq!(mode.to_string().parse::<SeedMode>(), Ok(mode));
+        }
+        assert!("pairs".parse::<SeedMode>().is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let (_, almanac) = example();
+        let steps = trace(79, &almanac);
+
+        let values: Vec<u64> = steps.iter().map(|(x, _)| *x).collect();
+        assert_eq!(values, vec![81, 81, 81, 74, 78, 78, 82]);
+
+        // seed 79 is moved into soil by the example's fifth line, then passes through
+        // fertilizer and water unchanged
+        let (_, mapping) = steps[0];
+        assert_eq!(
+            mapping.map(|m| (m.line, m.to_string())),
+            Some((5, "52 50 48".to_string()))
+        );
+        assert!(steps[1].1.is_none() && steps[2].1.is_none());
+    }
+
+    #[test]
+    fn test_best_seed() {
+        let (seeds, almanac) = example();
+        let ranges = seed_ranges(&seeds, SeedMode::Ranges).unwrap();
+
+        assert_eq!(best_seed(&ranges, &almanac), Some((79..93, 82)));
+        assert_eq!(trace(82, &almanac).last().map(|(x, _)| *x), Some(46));
+        assert_eq!(best_seed(&[], &almanac), None);
+
+        // part 1's answer, seed 13 landing on 35
+        let single = seed_ranges(&seeds, SeedMode::Individual).unwrap();
+        assert_eq!(best_seed(&single, &almanac), Some((13..14, 13)));
+        assert_eq!(trace(13, &almanac).last().map(|(x, _)| *x), Some(35));
+    }
+
+    #[test]
+    fn test_min_location_backwards() {
+        let (_, almanac) = example();
+        let seed_ranges = example_seed_ranges();
+
+        assert_eq!(min_location_backwards(&seed_ranges, &almanac), Some(46));
+        assert_eq!(min_location_backwards(&RangeSet::new(), &almanac), None);
+    }
+
+    fn seed_ranges_strategy() -> impl Strategy<Value = Vec<Range<u64>>> {
+        prop::collection::vec((0..80u64, 0..20u64).prop_map(|(s, l)| s..s + l), 0..4)
+    }
+
+    proptest! {
+        #[test]
+        fn min_location_matches_brute_force(
+            seed_ranges in seed_ranges_strategy(),
+            almanac in almanac(),
+        ) {
+            prop_assert_eq!(
+                min_location(&seed_ranges.iter().cloned().collect(), &almanac),
+                brute_force(&seed_ranges, &almanac)
+            );
+        }
+
+        #[test]
+        fn map_range_matches_map(
+            range in (0..80u64, 0..30u64).prop_map(|(s, l)| s..s + l),
+            almanac in almanac(),
+        ) {
+            assert_matches_points(&almanac[0], range);
+        }
+
+        #[test]
+        fn preimage_matches_map(
+            range in (0..80u64, 0..30u64).prop_map(|(s, l)| s..s + l),
+            almanac in almanac(),
+        ) {
+            // every value the almanac can produce below 100 comes from below 100
+            let expected: Vec<u64> = (0..100)
+                .filter(|&x| range.contains(&almanac[0].map(x)))
+                .collect();
+            let result: Vec<u64> = almanac[0]
+                .preimage(&range)
+                .into_iter()
+                .flatten()
+                .filter(|&x| x < 100)
+                .collect();
+
+            prop_assert_eq!(result, expected);
+        }
+
+        #[test]
+        fn min_location_backwards_matches_forwards(
+            seed_ranges in seed_ranges_strategy(),
+            almanac in almanac(),
+        ) {
+            let seed_ranges: RangeSet<u64> = seed_ranges.into_iter().collect();
+
+            prop_assert_eq!(
+                min_location_backwards(&seed_ranges, &almanac),
+                min_location(&seed_ranges, &almanac)
+            );
+        }
+    }
 }
@@ -0,0 +1,401 @@
+use crate::rangeset::RangeSet;
+use crate::ListMapping;
+use std::fmt::Write;
+use std::ops::Range;
+
+// values in start..end move by offset
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub start: u64,
+    pub end: u64,
+    pub offset: i128,
+}
+
+impl Segment {
+    fn apply(&self, x: u64) -> u64 {
+        (x as i128 + self.offset) as u64
+    }
+}
+
+// a function on 0..u64::MAX made of sorted, non-overlapping segments, every value outside
+// them maps to itself
+// no segment is empty or has a zero offset and touching segments with the same offset are
+// merged, so two equal functions always have equal segments
+#[derive(Clone, Debug, PartialEq)]
+pub struct Piecewise {
+    segments: Vec<Segment>,
+}
+
+impl Piecewise {
+    pub fn identity() -> Piecewise {
+        Piecewise {
+            segments: Vec::new(),
+        }
+    }
+
+    // one map, the last mapping holding a value moves it as in ListMapping
+    pub fn from_list_mapping(list_mapping: &ListMapping) -> Piecewise {
+        let mut bounds: Vec<u64> = list_mapping
+            .value
+            .iter()
+            .flat_map(|m| [m.source_range.start, m.source_range.end])
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        // one mapping or none is in force between each pair of boundaries
+        let segments = bounds.windows(2).map(|w| {
+            let offset = list_mapping
+                .value
+                .iter()
+                .rev()
+                .find(|m| m.source_range.contains(&w[0]))
+                .map_or(0, |m| {
+                    m.destination_range.start as i128 - m.source_range.start as i128
+                });
+
+            Segment {
+                start: w[0],
+                end: w[1],
+                offset,
+            }
+        });
+
+        Piecewise::normalise(segments)
+    }
+
+    // seed to location, each map in turn
+    pub fn compose(almanac: &[ListMapping]) -> Piecewise {
+        almanac
+            .iter()
+            .fold(Piecewise::identity(), |f, list_mapping| {
+                f.then(&Piecewise::from_list_mapping(list_mapping))
+            })
+    }
+
+    // sorted segments in, with empty and zero offset ones dropped and equal neighbours merged
+    fn normalise(segments: impl IntoIterator<Item = Segment>) -> Piecewise {
+        let mut result: Vec<Segment> = Vec::new();
+
+        for segment in segments {
+            if segment.start >= segment.end || segment.offset == 0 {
+                continue;
+            }
+            match result.last_mut() {
+                Some(last) if last.end == segment.start && last.offset == segment.offset => {
+                    last.end = segment.end;
+                }
+                _ => result.push(segment),
+            }
+        }
+
+        Piecewise { segments: result }
+    }
+
+    // the segments over r, with identity segments filling the gaps, found with one binary
+    // search for the first segment ending after r starts
+    fn pieces_over(&self, r: &Range<u64>) -> Vec<Segment> {
+        let mut result: Vec<Segment> = Vec::new();
+        let mut position = r.start;
+
+        let first = self.segments.partition_point(|s| s.end <= r.start);
+        for segment in self.segments[first..].iter() {
+            if position >= r.end {
+                break;
+            }
+            if segment.start > position {
+                result.push(Segment {
+                    start: position,
+                    end: segment.start.min(r.end),
+                    offset: 0,
+                });
+            }
+            result.push(Segment {
+                start: segment.start.max(position),
+                end: segment.end.min(r.end),
+                offset: segment.offset,
+            });
+            position = segment.end;
+        }
+        if position < r.end {
+            result.push(Segment {
+                start: position,
+                end: r.end,
+                offset: 0,
+            });
+        }
+
+        result.retain(|s| s.start < s.end);
+        result
+    }
+
+    pub fn apply(&self, x: u64) -> u64 {
+        let i = self.segments.partition_point(|s| s.end <= x);
+
+        match self.segments.get(i) {
+            Some(segment) if segment.start <= x => segment.apply(x),
+            _ => x,
+        }
+    }
+
+    // the image of a range
+    pub fn apply_range(&self, r: &Range<u64>) -> RangeSet<u64> {
+        self.pieces_over(r)
+            .into_iter()
+            .map(|s| s.apply(s.start)..s.apply(s.start) + (s.end - s.start))
+            .collect()
+    }
+
+    // self followed by next
+    pub fn then(&self, next: &Piecewise) -> Piecewise {
+        let mut result: Vec<Segment> = Vec::new();
+
+        for segment in self.pieces_over(&(0..u64::MAX)) {
+            let image = segment.apply(segment.start)
+                ..segment.apply(segment.start) + (segment.end - segment.start);
+
+            // each part of the image, moved back to where it came from
+            for piece in next.pieces_over(&image) {
+                result.push(Segment {
+                    start: (piece.start as i128 - segment.offset) as u64,
+                    end: (piece.end as i128 - segment.offset) as u64,
+                    offset: segment.offset + piece.offset,
+                });
+            }
+        }
+
+        // the images of neighbouring segments can land anywhere, so the parts come back out
+        // of order
+        result.sort_by_key(|s| s.start);
+        Piecewise::normalise(result)
+    }
+
+    // (values, offset here, offset in other) wherever the two functions differ
+    pub fn diff(&self, other: &Piecewise) -> Vec<(Range<u64>, i128, i128)> {
+        let mut bounds: Vec<u64> = self
+            .segments
+            .iter()
+            .chain(other.segments.iter())
+            .flat_map(|s| [s.start, s.end])
+            .collect();
+        bounds.sort();
+        bounds.dedup();
+
+        let mut result: Vec<(Range<u64>, i128, i128)> = Vec::new();
+
+        for w in bounds.windows(2) {
+            let (a, b) = (self.offset_at(w[0]), other.offset_at(w[0]));
+            if a == b {
+                continue;
+            }
+            match result.last_mut() {
+                Some((range, x, y)) if range.end == w[0] && (*x, *y) == (a, b) => {
+                    range.end = w[1];
+                }
+                _ => result.push((w[0]..w[1], a, b)),
+            }
+        }
+
+        result
+    }
+
+    fn offset_at(&self, x: u64) -> i128 {
+        self.apply(x) as i128 - x as i128
+    }
+
+    pub fn to_table(&self) -> String {
+        let mut result = String::new();
+
+        writeln!(
+            result,
+            "{:>20}  {:>20}  {:>20}  {:>20}  {:>21}",
+            "seed start", "seed end", "location start", "location end", "offset"
+        )
+        .unwrap();
+        for s in self.pieces_over(&(0..u64::MAX)) {
+            writeln!(
+                result,
+                "{:>20}  {:>20}  {:>20}  {:>20}  {:>+21}",
+                s.start,
+                s.end,
+                s.apply(s.start),
+                s.apply(s.start) + (s.end - s.start),
+                s.offset
+            )
+            .unwrap();
+        }
+
+        result
+    }
+}
+
+pub fn diff_table(diff: &[(Range<u64>, i128, i128)]) -> String {
+    let mut result = String::new();
+
+    writeln!(
+        result,
+        "{:>20}  {:>20}  {:>21}  {:>21}",
+        "seed start", "seed end", "offset before", "offset after"
+    )
+    .unwrap();
+    for (range, a, b) in diff {
+        writeln!(
+            result,
+            "{:>20}  {:>20}  {:>+21}  {:>+21}",
+            range.start, range.end, a, b
+        )
+        .unwrap();
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{almanac, example, example_seed_ranges};
+    use crate::min_location;
+    use proptest::prelude::*;
+
+    fn segment(start: u64, end: u64, offset: i128) -> Segment {
+        Segment { start, end, offset }
+    }
+
+    #[test]
+    fn test_from_list_mapping() {
+        let list_mapping = ListMapping::from(vec![vec![50, 98, 2], vec![52, 50, 48]]);
+        let f = Piecewise::from_list_mapping(&list_mapping);
+
+        assert_eq!(f.segments, &[segment(50, 98, 2), segment(98, 100, -48)]);
+        assert_eq!(f.apply(49), 49);
+        assert_eq!(f.apply(50), 52);
+        assert_eq!(f.apply(99), 51);
+        assert_eq!(f.apply(100), 100);
+    }
+
+    #[test]
+    fn test_normalise() {
+        // touching mappings moving by the same amount become one segment
+        let list_mapping = ListMapping::from(vec![vec![15, 10, 5], vec![20, 15, 5], vec![7, 7, 3]]);
+
+        assert_eq!(
+            Piecewise::from_list_mapping(&list_mapping).segments,
+            &[segment(10, 20, 5)]
+        );
+    }
+
+    #[test]
+    fn test_compose() {
+        let (seeds, almanac) = example();
+        let f = Piecewise::compose(&almanac);
+
+        // the example's seeds, one by one
+        let locations: Vec<u64> = seeds.iter().map(|&x| f.apply(x)).collect();
+        assert_eq!(locations, vec![82, 43, 86, 35]);
+
+        let min = example_seed_ranges()
+            .iter()
+            .flat_map(|r| f.apply_range(r))
+            .map(|r| r.start)
+            .min();
+        assert_eq!(min, Some(46));
+    }
+
+    #[test]
+    fn test_then_inverse() {
+        // 10..20 -> 30..40 followed by 30..40 -> 10..20 is the identity
+        let f = Piecewise::from_list_mapping(&ListMapping::from(vec![vec![30, 10, 10]]));
+        let g = Piecewise::from_list_mapping(&ListMapping::from(vec![vec![10, 30, 10]]));
+
+        assert_eq!(
+            f.then(&g).segments,
+            &[segment(30, 40, -20)],
+            "30..40 still moves, 10..20 comes back to itself"
+        );
+    }
+
+    #[test]
+    fn test_apply_range() {
+        let list_mapping = ListMapping::from(vec![vec![50, 98, 2], vec![52, 50, 48]]);
+        let f = Piecewise::from_list_mapping(&list_mapping);
+
+        assert_eq!(f.apply_range(&(40..52)).as_slice(), vec![40..50, 52..54]);
+        // 95..98 and 100..105 land next to each other and are merged
+        assert_eq!(f.apply_range(&(95..105)).as_slice(), vec![50..52, 97..105]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let (_, almanac) = example();
+        let before = Piecewise::compose(&almanac);
+
+        // move the last map's 93..97 somewhere else
+        let mut changed = almanac.clone();
+        changed[6] = ListMapping::from(vec![vec![60, 56, 37], vec![200, 93, 4]]);
+        let after = Piecewise::compose(&changed);
+
+        let diff = before.diff(&after);
+        assert!(!diff.is_empty());
+        for (range, a, b) in &diff {
+            assert_ne!(a, b);
+            assert_eq!(before.apply(range.start) as i128 - range.start as i128, *a);
+            assert_eq!(after.apply(range.start) as i128 - range.start as i128, *b);
+        }
+        // every seed outside the diff lands in the same place
+        for x in 0..200 {
+            if !diff.iter().any(|(range, _, _)| range.contains(&x)) {
+                assert_eq!(before.apply(x), after.apply(x));
+            }
+        }
+
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn test_to_table() {
+        let f = Piecewise::from_list_mapping(&ListMapping::from(vec![vec![50, 98, 2]]));
+        let table = f.to_table();
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            vec!["98", "100", "50", "52", "-48"]
+        );
+        assert!(lines[3].trim_end().ends_with("+0"));
+    }
+
+    proptest! {
+        #[test]
+        fn compose_matches_maps(almanac in almanac(), x in 0..100u64) {
+            let f = Piecewise::compose(&almanac);
+            let expected = almanac.iter().fold(x, |x, map| map.map(x));
+
+            prop_assert_eq!(f.apply(x), expected);
+        }
+
+        #[test]
+        fn compose_is_normalised(almanac in almanac()) {
+            let segments = Piecewise::compose(&almanac).segments;
+
+            for s in &segments {
+                prop_assert!(s.start < s.end && s.offset != 0);
+            }
+            for w in segments.windows(2) {
+                let touching = w[0].end == w[1].start;
+                prop_assert!(w[0].end < w[1].start || (touching && w[0].offset != w[1].offset));
+            }
+        }
+
+        #[test]
+        fn apply_range_matches_min_location(
+            almanac in almanac(),
+            r in (0..80u64, 0..20u64).prop_map(|(s, l)| s..s + l),
+        ) {
+            let f = Piecewise::compose(&almanac);
+            let min = f.apply_range(&r).min();
+
+            prop_assert_eq!(min, min_location(&RangeSet::from(r), &almanac));
+        }
+    }
+}
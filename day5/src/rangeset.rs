@@ -0,0 +1,308 @@
+use std::ops::Range;
+
+// half open ranges of T, kept sorted with empty ones dropped and any that overlap or meet end
+// to end merged, so two sets holding the same values always hold the same ranges
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T: Ord + Copy> RangeSet<T> {
+    pub fn new() -> RangeSet<T> {
+        RangeSet { ranges: Vec::new() }
+    }
+
+    pub fn as_slice(&self) -> &[Range<T>] {
+        &self.ranges
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Range<T>> {
+        self.ranges.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    pub fn insert(&mut self, r: Range<T>) {
+        if r.is_empty() {
+            return;
+        }
+
+        // the ranges r overlaps or meets, all replaced by one covering them and r
+        let first = self.ranges.partition_point(|x| x.end < r.start);
+        let last = self.ranges.partition_point(|x| x.start <= r.end);
+
+        let merged = match first < last {
+            true => r.start.min(self.ranges[first].start)..r.end.max(self.ranges[last - 1].end),
+            false => r,
+        };
+        self.ranges.splice(first..last, [merged]);
+    }
+
+    pub fn contains(&self, x: T) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= x);
+
+        self.ranges.get(i).is_some_and(|r| r.start <= x)
+    }
+
+    // every value of r is in the set
+    pub fn contains_range(&self, r: &Range<T>) -> bool {
+        if r.is_empty() {
+            return true;
+        }
+        let i = self.ranges.partition_point(|x| x.end <= r.start);
+
+        self.ranges
+            .get(i)
+            .is_some_and(|x| x.start <= r.start && r.end <= x.end)
+    }
+
+    // some value of r is in the set
+    pub fn overlaps(&self, r: &Range<T>) -> bool {
+        let i = self.ranges.partition_point(|x| x.end <= r.start);
+
+        !r.is_empty() && self.ranges.get(i).is_some_and(|x| x.start < r.end)
+    }
+
+    pub fn union(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        self.iter().chain(other.iter()).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result: Vec<Range<T>> = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        // step past whichever range ends first
+        while let (Some(a), Some(b)) = (self.ranges.get(i), other.ranges.get(j)) {
+            let start = a.start.max(b.start);
+            let end = a.end.min(b.end);
+            if start < end {
+                result.push(start..end);
+            }
+            match a.end <= b.end {
+                true => i += 1,
+                false => j += 1,
+            }
+        }
+
+        result.into_iter().collect()
+    }
+
+    pub fn difference(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result: Vec<Range<T>> = Vec::new();
+
+        for r in &self.ranges {
+            let mut start = r.start;
+
+            let first = other.ranges.partition_point(|x| x.end <= r.start);
+            for cut in other.ranges[first..].iter().take_while(|x| x.start < r.end) {
+                if start < cut.start {
+                    result.push(start..cut.start);
+                }
+                start = start.max(cut.end);
+            }
+            if start < r.end {
+                result.push(start..r.end);
+            }
+        }
+
+        result.into_iter().collect()
+    }
+
+    // every value within bounds that isn't in the set
+    pub fn complement(&self, bounds: Range<T>) -> RangeSet<T> {
+        RangeSet::from(bounds).difference(self)
+    }
+}
+
+impl<T: Ord + Copy> Default for RangeSet<T> {
+    fn default() -> Self {
+        RangeSet::new()
+    }
+}
+
+impl<T: Ord + Copy> From<Range<T>> for RangeSet<T> {
+    fn from(r: Range<T>) -> Self {
+        let mut result = RangeSet::new();
+        result.insert(r);
+        result
+    }
+}
+
+impl<T: Ord + Copy> FromIterator<Range<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut v: Vec<Range<T>> = iter.into_iter().filter(|r| !r.is_empty()).collect();
+        v.sort_by_key(|r| r.start);
+
+        let mut ranges: Vec<Range<T>> = Vec::new();
+
+        for range in v {
+            match ranges.last_mut() {
+                // Ranges overlap or touch, so merge them
+                Some(current_range) if range.start <= current_range.end => {
+                    current_range.end = current_range.end.max(range.end);
+                }
+                // Ranges don't overlap, so start a new one
+                _ => ranges.push(range),
+            }
+        }
+
+        RangeSet { ranges }
+    }
+}
+
+impl<T: Ord + Copy> Extend<Range<T>> for RangeSet<T> {
+    fn extend<I: IntoIterator<Item = Range<T>>>(&mut self, iter: I) {
+        for r in iter {
+            self.insert(r);
+        }
+    }
+}
+
+impl<T> IntoIterator for RangeSet<T> {
+    type Item = Range<T>;
+    type IntoIter = std::vec::IntoIter<Range<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ranges.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RangeSet<T> {
+    type Item = &'a Range<T>;
+    type IntoIter = std::slice::Iter<'a, Range<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ranges.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_from_iter() {
+        let set: RangeSet<u64> = vec![5..8, 0..2, 2..3, 7..10, 4..4].into_iter().collect();
+        assert_eq!(set.as_slice(), [0..3, 5..10]);
+
+        let set: RangeSet<u64> = Vec::new().into_iter().collect();
+        assert!(set.is_empty());
+        assert_eq!(set.min(), None);
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut set: RangeSet<i32> = vec![-10..-5, 0..2, 8..9].into_iter().collect();
+
+        set.insert(-5..0);
+        assert_eq!(set.as_slice(), [-10..2, 8..9]);
+        set.insert(4..6);
+        assert_eq!(set.as_slice(), [-10..2, 4..6, 8..9]);
+        set.insert(3..3);
+        assert_eq!(set.as_slice(), [-10..2, 4..6, 8..9]);
+        set.insert(1..20);
+        assert_eq!(set, RangeSet::from(-10..20));
+    }
+
+    #[test]
+    fn test_algebra() {
+        let a: RangeSet<u8> = vec![0..10, 20..30].into_iter().collect();
+        let b: RangeSet<u8> = RangeSet::from(5..25);
+
+        assert_eq!(a.union(&b).as_slice(), [Range { start: 0, end: 30 }]);
+        assert_eq!(a.intersection(&b).as_slice(), [5..10, 20..25]);
+        assert_eq!(a.difference(&b).as_slice(), [0..5, 25..30]);
+        assert_eq!(b.difference(&a).as_slice(), [Range { start: 10, end: 20 }]);
+        assert_eq!(a.complement(0..u8::MAX).as_slice(), [10..20, 30..u8::MAX]);
+    }
+
+    #[test]
+    fn test_contains() {
+        let set: RangeSet<u16> = vec![10..20, 30..40].into_iter().collect();
+
+        assert!(set.contains(10) && set.contains(19) && set.contains(30));
+        assert!(!set.contains(20) && !set.contains(9) && !set.contains(40));
+
+        assert!(set.contains_range(&(12..20)));
+        assert!(!set.contains_range(&(15..35)));
+        assert!(set.contains_range(&(25..25)));
+
+        assert!(set.overlaps(&(19..30)));
+        assert!(!set.overlaps(&(20..30)));
+        assert!(!set.overlaps(&(35..35)));
+    }
+
+    // the values of a set within 0..64, one at a time
+    fn points(set: &RangeSet<u8>) -> BTreeSet<u8> {
+        set.iter().flat_map(|r| r.clone()).collect()
+    }
+
+    fn range_set() -> impl Strategy<Value = (Vec<Range<u8>>, RangeSet<u8>)> {
+        prop::collection::vec((0..64u8, 0..64u8).prop_map(|(a, b)| a..b), 0..6).prop_map(|v| {
+            let set = v.iter().cloned().collect();
+            (v, set)
+        })
+    }
+
+    fn assert_normalised(set: &RangeSet<u8>) -> Result<(), TestCaseError> {
+        for r in set.iter() {
+            prop_assert!(r.start < r.end);
+        }
+        for w in set.as_slice().windows(2) {
+            prop_assert!(w[0].end < w[1].start);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn from_iter_keeps_values((v, set) in range_set()) {
+            let expected: BTreeSet<u8> = v.iter().flat_map(|r| r.clone()).collect();
+
+            assert_normalised(&set)?;
+            prop_assert_eq!(points(&set), expected);
+        }
+
+        #[test]
+        fn insert_matches_from_iter((v, set) in range_set()) {
+            let mut inserted = RangeSet::new();
+            inserted.extend(v);
+
+            prop_assert_eq!(inserted, set);
+        }
+
+        #[test]
+        fn algebra_matches_points((_, a) in range_set(), (_, b) in range_set()) {
+            let (x, y) = (points(&a), points(&b));
+
+            for (result, expected) in [
+                (a.union(&b), x.union(&y).copied().collect::<BTreeSet<u8>>()),
+                (a.intersection(&b), x.intersection(&y).copied().collect()),
+                (a.difference(&b), x.difference(&y).copied().collect()),
+                (a.complement(10..50), (10..50).filter(|n| !x.contains(n)).collect()),
+            ] {
+                assert_normalised(&result)?;
+                prop_assert_eq!(points(&result), expected);
+            }
+        }
+
+        #[test]
+        fn queries_match_points((_, set) in range_set(), start in 0..70u8, length in 0..10u8) {
+            let x = points(&set);
+            let r = start..start + length;
+
+            prop_assert_eq!(set.contains(start), x.contains(&start));
+            prop_assert_eq!(set.contains_range(&r), r.clone().all(|n| x.contains(&n)));
+            prop_assert_eq!(set.overlaps(&r), r.clone().any(|n| x.contains(&n)));
+            prop_assert_eq!(set.min(), x.first().copied());
+        }
+    }
+}
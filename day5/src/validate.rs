@@ -0,0 +1,399 @@
+use crate::graph::CategoryMap;
+use crate::rangeset::RangeSet;
+use crate::{ListMapping, Mapping};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+// one line of a map as written, "50 98 2"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub line: usize,
+    pub destination: u64,
+    pub source: u64,
+    pub length: u64,
+}
+
+impl Row {
+    // None when either range runs past u64::MAX
+    fn source_range(&self) -> Option<Range<u64>> {
+        Some(self.source..self.source.checked_add(self.length)?)
+    }
+
+    fn fits(&self) -> bool {
+        self.source.checked_add(self.length).is_some()
+            && self.destination.checked_add(self.length).is_some()
+    }
+
+    fn offset(&self) -> i128 {
+        self.destination as i128 - self.source as i128
+    }
+}
+
+// one map as written, line is the header's
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    pub line: usize,
+    pub source: String,
+    pub destination: String,
+    pub rows: Vec<Row>,
+}
+
+// what to do with an almanac that has errors in it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Validation {
+    Reject,
+    // later mappings keep the values they share with earlier ones, mappings running past
+    // u64::MAX are cut short, empty ones dropped and a map with no mappings keeps every value
+    Resolve,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueKind {
+    Overlap,
+    Overflow,
+    Empty,
+    // source ranges meeting end to end, only ever reported
+    Adjacent,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub line: usize,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+impl Issue {
+    pub fn is_error(&self) -> bool {
+        self.kind != IssueKind::Adjacent
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Issue {}
+
+// every issue in the almanac, in line order
+pub fn check(sections: &[Section]) -> Vec<Issue> {
+    let mut result: Vec<Issue> = Vec::new();
+
+    for section in sections {
+        if section.rows.is_empty() {
+            result.push(Issue {
+                line: section.line,
+                kind: IssueKind::Empty,
+                message: format!(
+                    "{}-to-{} map has no mappings",
+                    section.source, section.destination
+                ),
+            });
+        }
+
+        for (i, row) in section.rows.iter().enumerate() {
+            let issue = |kind: IssueKind, message: String| Issue {
+                line: row.line,
+                kind,
+                message,
+            };
+
+            if !row.fits() {
+                result.push(issue(
+                    IssueKind::Overflow,
+                    format!(
+                        "{} {} {} runs past {}",
+                        row.destination,
+                        row.source,
+                        row.length,
+                        u64::MAX
+                    ),
+                ));
+                continue;
+            }
+            if row.length == 0 {
+                result.push(issue(
+                    IssueKind::Empty,
+                    format!("{} {} 0 maps nothing", row.destination, row.source),
+                ));
+                continue;
+            }
+
+            let source = row.source_range().unwrap();
+            for earlier in section.rows[..i]
+                .iter()
+                .filter(|x| x.fits() && x.length > 0)
+            {
+                let other = earlier.source_range().unwrap();
+
+                if source.start < other.end && other.start < source.end {
+                    result.push(issue(
+                        IssueKind::Overlap,
+                        format!(
+                            "source {}..{} overlaps {}..{} from line {}",
+                            source.start, source.end, other.start, other.end, earlier.line
+                        ),
+                    ));
+                } else if source.start == other.end || other.start == source.end {
+                    result.push(issue(
+                        IssueKind::Adjacent,
+                        format!(
+                            "source {}..{} meets {}..{} from line {}",
+                            source.start, source.end, other.start, other.end, earlier.line
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+// the maps, or every error in them when rejecting
+pub fn resolve(sections: Vec<Section>, mode: Validation) -> Result<Vec<CategoryMap>, Vec<Issue>> {
+    if mode == Validation::Reject {
+        let errors: Vec<Issue> = check(&sections)
+            .into_iter()
+            .filter(|issue| issue.is_error())
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+    }
+
+    Ok(sections
+        .into_iter()
+        .map(|section| CategoryMap {
+            list_mapping: resolve_rows(&section.rows),
+            source: section.source,
+            destination: section.destination,
+        })
+        .collect())
+}
+
+// sorted mappings with no two sources overlapping, moving every value as the rows would
+// each keeps the line of its row, so one move written as two rows stays two mappings
+fn resolve_rows(rows: &[Row]) -> ListMapping {
+    // (source range, row), later rows claiming their whole source first
+    let mut claimed: Vec<(Range<u64>, &Row)> = Vec::new();
+    let mut taken: RangeSet<u64> = RangeSet::new();
+
+    for row in rows.iter().rev() {
+        let length = row.length.min(u64::MAX - row.source.max(row.destination));
+        let pieces = RangeSet::from(row.source..row.source + length).difference(&taken);
+
+        taken = taken.union(&pieces);
+        claimed.extend(pieces.into_iter().map(|r| (r, row)));
+    }
+
+    claimed.sort_by_key(|(r, _)| r.start);
+
+    ListMapping {
+        value: claimed
+            .into_iter()
+            .map(|(r, row)| Mapping {
+                destination_range: (r.start as i128 + row.offset()) as u64
+                    ..(r.end as i128 + row.offset()) as u64,
+                source_range: r,
+                line: row.line,
+            })
+            .collect(),
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Validation::Reject => write!(f, "reject"),
+            Validation::Resolve => write!(f, "resolve"),
+        }
+    }
+}
+
+// Example input: "reject" or "resolve"
+impl FromStr for Validation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "reject" => Ok(Validation::Reject),
+            "resolve" => Ok(Validation::Resolve),
+            other => Err(format!(
+                "unknown validation `{}`, expected reject or resolve",
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_input;
+    use proptest::prelude::*;
+
+    fn row(line: usize, destination: u64, source: u64, length: u64) -> Row {
+        Row {
+            line,
+            destination,
+            source,
+            length,
+        }
+    }
+
+    fn section(rows: Vec<Row>) -> Section {
+        Section {
+            line: 1,
+            source: "seed".to_string(),
+            destination: "soil".to_string(),
+            rows,
+        }
+    }
+
+    fn kinds(issues: &[Issue]) -> Vec<(usize, IssueKind)> {
+        issues
+            .iter()
+            .map(|issue| (issue.line, issue.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let issues = check(&[section(vec![
+            row(2, 50, 98, 2),
+            row(3, 52, 50, 48),
+            row(4, 0, 90, 10),
+            row(5, 7, 7, 0),
+            row(6, 0, u64::MAX - 1, 2),
+        ])]);
+
+        assert_eq!(
+            kinds(&issues),
+            vec![
+                (3, IssueKind::Adjacent),
+                (4, IssueKind::Overlap),
+                (4, IssueKind::Overlap),
+                (5, IssueKind::Empty),
+                (6, IssueKind::Overflow),
+            ]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "line 4: source 90..100 overlaps 98..100 from line 2"
+        );
+        assert_eq!(
+            issues[4].to_string(),
+            "line 6: 0 18446744073709551614 2 runs past 18446744073709551615"
+        );
+
+        assert_eq!(
+            kinds(&check(&[section(vec![])])),
+            vec![(1, IssueKind::Empty)]
+        );
+    }
+
+    #[test]
+    fn test_resolve_reject() {
+        let sections = vec![section(vec![row(2, 50, 98, 2), row(3, 0, 99, 5)])];
+        let errors = resolve(sections, Validation::Reject).unwrap_err();
+
+        assert_eq!(kinds(&errors), vec![(3, IssueKind::Overlap)]);
+
+        // touching sources alone are fine
+        let sections = vec![section(vec![row(2, 50, 98, 2), row(3, 52, 50, 48)])];
+        assert!(resolve(sections, Validation::Reject).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_rows() {
+        // 99 goes to the later row, 98 is left to the earlier one
+        let list_mapping = resolve_rows(&[row(2, 50, 98, 2), row(3, 0, 99, 5)]);
+        let sources: Vec<Range<u64>> = list_mapping
+            .value
+            .iter()
+            .map(|m| m.source_range.clone())
+            .collect();
+
+        assert_eq!(sources, vec![98..99, 99..104]);
+        assert_eq!(list_mapping.map(98), 50);
+        assert_eq!(list_mapping.map(99), 0);
+
+        assert_eq!(list_mapping.value[0].line, 2);
+        assert_eq!(list_mapping.value[1].line, 3);
+
+        // one move written as two rows keeps both lines, an empty row disappears
+        let list_mapping = resolve_rows(&[row(2, 10, 0, 5), row(3, 15, 5, 5), row(4, 1, 1, 0)]);
+        let lines: Vec<usize> = list_mapping.value.iter().map(|m| m.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+        assert_eq!(list_mapping.map(7), 17);
+
+        // cut short at the last value
+        let list_mapping = resolve_rows(&[row(2, 0, u64::MAX - 1, 5)]);
+        assert_eq!(list_mapping.value[0].source_range, u64::MAX - 1..u64::MAX);
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let input = "seeds: 1 2
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-water map:
+
+water-to-light map:
+1 2 3
+";
+        let (_, (_, sections)) = parse_input(input).unwrap();
+
+        assert_eq!(sections.len(), 3);
+        assert_eq!(sections[0].line, 3);
+        assert_eq!(sections[0].rows[1], row(5, 52, 50, 48));
+        assert!(sections[1].rows.is_empty());
+        assert_eq!(sections[2].rows[0].line, 10);
+
+        let issues = check(&sections);
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "line 5: source 50..98 meets 98..100 from line 4",
+                "line 7: soil-to-water map has no mappings"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validation_from_str() {
+        for mode in [Validation::Reject, Validation::Resolve] {
+            assert_eq!(mode.to_string().parse::<Validation>(), Ok(mode));
+        }
+        assert!("fix".parse::<Validation>().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn resolve_rows_matches_map(
+            v in prop::collection::vec((0..60u64, 0..60u64, 0..20u64), 0..6),
+        ) {
+            let rows: Vec<Row> = v.iter().map(|&(d, s, l)| row(1, d, s, l)).collect();
+            let list_mapping =
+                ListMapping::from(v.iter().map(|&(d, s, l)| vec![d, s, l]).collect());
+            let resolved = resolve_rows(&rows);
+
+            for w in resolved.value.windows(2) {
+                prop_assert!(w[0].source_range.end <= w[1].source_range.start);
+            }
+            for x in 0..100 {
+                prop_assert_eq!(resolved.map(x), list_mapping.map(x));
+            }
+        }
+    }
+}